@@ -0,0 +1,209 @@
+use crate::config::Directories;
+use crate::connection::Connection;
+use crate::image::{self, Image};
+use crate::libs::Data;
+use crate::worker::Request;
+
+use mpd::Song;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tui::{buffer::Buffer, layout::Rect, style::Color, widgets::Widget};
+
+/// Pictures are shrunk to fit this many pixels a side once decoded, which is
+/// about what a terminal drawing pixels gives a cover, and far more than it
+/// shows with two pixels to a cell.
+const SIZE: usize = 512;
+
+/// Albums whose pictures are kept, the ones played longest ago going first.
+/// A picture takes up to 768 KiB.
+const KEPT: usize = 8;
+
+/// Names of pictures looked for next to the song, best first.
+const NAMES: [&str; 4] = ["cover", "folder", "front", "album"];
+const EXTENSIONS: [&str; 3] = ["jpg", "jpeg", "png"];
+
+/// What the worker needs to find the picture of one album.
+pub struct Lookup {
+    pub key: String,
+    /// Any song on the album.
+    pub file: String,
+}
+
+/// Cover art of the albums played so far, decoded once each.
+#[derive(Default)]
+pub struct Cache {
+    /// `None` for albums found to have no picture.
+    images: HashMap<String, Option<Image>>,
+    /// Keys of `images`, the one wanted last at the back.
+    order: VecDeque<String>,
+    /// Album asked for and not back yet.
+    pending: Option<String>,
+    /// Whether the terminal draws the pictures in pixels over blank cells.
+    pub pixels: bool,
+}
+
+impl Cache {
+    /// The picture of the album `song` is on, once it is loaded.
+    pub fn get(&self, song: &Song) -> Option<&Image> {
+        self.images.get(&key(song))?.as_ref()
+    }
+
+    /// Whether the picture of the album `song` is on was looked for yet,
+    /// whether or not one was found.
+    pub fn searched(&self, song: &Song) -> bool {
+        self.images.contains_key(&key(song))
+    }
+
+    pub fn loaded(&mut self, key: String, image: Option<Image>) {
+        if self.pending.as_ref() == Some(&key) {
+            self.pending = None;
+        }
+        self.touch(&key);
+        self.images.insert(key, image);
+        while self.order.len() > KEPT {
+            if let Some(old) = self.order.pop_front() {
+                self.images.remove(&old);
+            }
+        }
+    }
+
+    /// Moves `key` to the back of the line for eviction.
+    fn touch(&mut self, key: &str) {
+        self.order.retain(|k| k != key);
+        self.order.push_back(key.to_string());
+    }
+}
+
+/// Albums go by album artist and title, or by directory when untagged.
+pub fn key(song: &Song) -> String {
+    let artist = song
+        .tags
+        .get("AlbumArtist")
+        .or_else(|| song.tags.get("Artist"));
+    match (artist, song.tags.get("Album")) {
+        (Some(artist), Some(album)) => format!("{}\n{}", artist, album),
+        _ => directory(&song.file).to_string(),
+    }
+}
+
+fn directory(file: &str) -> &str {
+    file.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+/// Asks the worker for the picture of the album playing, unless it is loaded
+/// or on its way.
+pub fn want(data: &mut Data) {
+    if data.current.file.is_empty() {
+        return;
+    }
+    let key = key(&data.current);
+    if data.art.images.contains_key(&key) {
+        data.art.touch(&key);
+        return;
+    }
+    if data.art.pending.as_ref() == Some(&key) {
+        return;
+    }
+    data.art.pending = Some(key.clone());
+    data.requests.push(Request::Art(Lookup {
+        key,
+        file: data.current.file.clone(),
+    }));
+}
+
+/// Finds and decodes a picture for the album: the one mpd keeps next to the
+/// song, then the one embedded in it, then one in the music directory.
+pub fn fetch(connection: &Connection, directories: &Directories, lookup: &Lookup) -> Option<Image> {
+    for command in &["albumart", "readpicture"] {
+        if let Ok(Some(bytes)) = connection.picture(command, &lookup.file) {
+            if let Some(image) = image::decode(&bytes) {
+                return Some(image.fit(SIZE));
+            }
+        }
+    }
+    let mut pictures: Vec<(usize, PathBuf)> =
+        fs::read_dir(directories.beside(connection, &lookup.file)?)
+            .ok()?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                Some((rank(&path)?, path))
+            })
+            .collect();
+    pictures.sort();
+    pictures
+        .iter()
+        .filter(|(_, path)| fs::metadata(path).is_ok_and(|m| m.len() <= image::MAX_BYTES as u64))
+        .find_map(|(_, path)| image::decode(&fs::read(path).ok()?))
+        .map(|image| image.fit(SIZE))
+}
+
+/// Where in `NAMES` the picture at `path` comes, if it is one.
+fn rank(path: &Path) -> Option<usize> {
+    let lower = |s: Option<&std::ffi::OsStr>| s?.to_str().map(str::to_lowercase);
+    let extension = lower(path.extension())?;
+    if !EXTENSIONS.contains(&extension.as_str()) {
+        return None;
+    }
+    let stem = lower(path.file_stem())?;
+    NAMES.iter().position(|name| *name == stem)
+}
+
+/// Draws a picture as large as fits, two pixels to a cell: the upper one as
+/// the colour of a `▀` and the lower one as the background.
+pub struct Cover<'a> {
+    image: &'a Image,
+}
+
+impl<'a> Cover<'a> {
+    pub fn new(image: &'a Image) -> Cover<'a> {
+        Cover { image }
+    }
+}
+
+impl<'a> Widget for Cover<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let (width, height) = (area.width as usize, area.height as usize * 2);
+        if width == 0 || height == 0 {
+            return;
+        }
+        let scale =
+            (width as f64 / self.image.width as f64).min(height as f64 / self.image.height as f64);
+        let width = ((self.image.width as f64 * scale) as usize).clamp(1, width);
+        let height = ((self.image.height as f64 * scale) as usize).clamp(1, height);
+        let image = self.image.resize(width, height);
+        for y in (0..height).step_by(2) {
+            for x in 0..width {
+                let cell = buf.get_mut(area.x + x as u16, area.y + (y / 2) as u16);
+                cell.set_symbol("▀").set_fg(rgb(image.pixel(x, y)));
+                if y + 1 < height {
+                    cell.set_bg(rgb(image.pixel(x, y + 1)));
+                }
+            }
+        }
+    }
+}
+
+fn rgb([r, g, b]: [u8; 3]) -> Color {
+    Color::Rgb(r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eviction() {
+        let mut cache = Cache::default();
+        for i in 0..KEPT {
+            cache.loaded(i.to_string(), None);
+        }
+        // Wanting the first again saves it from going next.
+        cache.touch("0");
+        cache.loaded(String::from("new"), Some(Image::new(1, 1, vec![[0; 3]])));
+        assert_eq!(cache.images.len(), KEPT);
+        assert!(cache.images.contains_key("0"));
+        assert!(!cache.images.contains_key("1"));
+        assert!(cache.images["new"].is_some());
+    }
+}
@@ -0,0 +1,355 @@
+use crate::connection;
+
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+usage: flux [options] [command]
+
+Without a command flux opens its interface. A command controls mpd and exits.
+
+commands:
+  play [<position>]          play, from a queue position if given
+  pause                      pause
+  toggle                     play or pause
+  stop                       stop
+  next                       play the next song
+  prev                       play the previous song
+  vol [[+|-]<percent>]       print the volume, set it, or change it by a step
+  seek [+|-]<[m:]ss> | <n>%  seek to a time or share of the song, or by a step
+  add <query>                queue the songs whose artist, album or title match
+  status [-f <format>]       print what is playing, or fill in a format such as
+                             '%artist% - %title%' with tags and %elapsed%,
+                             %time%, %percent%, %state%, %volume%, %position%
+  send <message>             pass a message to the running flux: tab <name>,
+                             search [<text>], notify <text> or action <name>
+
+options:
+  -h, --host <host>          mpd host, socket path or password@host
+  -p, --port <port>          mpd port
+  -P, --password <password>  password sent after connecting
+  -c, --config <path>        config file to read
+      --help                 print this message
+
+exit status: 0 on success, 1 when a command fails or nothing matches, 2 for
+bad usage, 3 when mpd cannot be reached.";
+
+/// Command line arguments.
+#[derive(Debug, Default)]
+pub struct Args {
+    pub connection: connection::Settings,
+    pub config: Option<PathBuf>,
+    pub help: bool,
+    pub command: Option<Command>,
+}
+
+/// A value given outright, or as a step from the current one when signed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Amount {
+    To(i64),
+    By(i64),
+}
+
+impl Amount {
+    fn parse(arg: &str, value: impl Fn(&str) -> Option<i64>) -> Option<Amount> {
+        match arg.as_bytes().first()? {
+            b'+' => value(&arg[1..]).map(Amount::By),
+            b'-' => value(&arg[1..]).map(|v| Amount::By(-v)),
+            _ => value(arg).map(Amount::To),
+        }
+    }
+
+    pub fn from(self, current: i64) -> i64 {
+        match self {
+            Amount::To(value) => value,
+            Amount::By(step) => current + step,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Seek {
+    /// Seconds into the song.
+    Time(Amount),
+    Percent(u8),
+}
+
+/// A command run against mpd in place of the interface.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Plays, from the given queue position when there is one, counting from 1.
+    Play(Option<u32>),
+    Pause,
+    Toggle,
+    Stop,
+    Next,
+    Prev,
+    /// Sets or changes the volume, or prints it without an amount.
+    Volume(Option<Amount>),
+    Seek(Seek),
+    Add(String),
+    /// Prints the status, filled into the format when one is given.
+    Status(Option<String>),
+    /// Passes a message to the running flux.
+    Send(String),
+}
+
+impl Command {
+    fn parse(name: &str, args: Vec<String>) -> Result<Command, String> {
+        let usage = |what: &str| format!("usage: flux {}", what);
+        // The argument of commands that take just one.
+        let one = match args.as_slice() {
+            [arg] => Some(arg.as_str()),
+            _ => None,
+        };
+        let command = match name {
+            "play" if args.is_empty() => Command::Play(None),
+            "play" => one
+                .and_then(|position| position.parse().ok())
+                .filter(|position| *position > 0)
+                .map(|position| Command::Play(Some(position)))
+                .ok_or_else(|| usage("play [<position>]"))?,
+            "pause" | "toggle" | "stop" | "next" | "prev" if !args.is_empty() => {
+                return Err(usage(name))
+            }
+            "pause" => Command::Pause,
+            "toggle" => Command::Toggle,
+            "stop" => Command::Stop,
+            "next" => Command::Next,
+            "prev" => Command::Prev,
+            "vol" if args.is_empty() => Command::Volume(None),
+            "vol" => one
+                .and_then(|arg| Amount::parse(arg, |v| v.parse().ok()))
+                .map(|amount| Command::Volume(Some(amount)))
+                .ok_or_else(|| usage("vol [[+|-]<percent>]"))?,
+            "seek" => one
+                .and_then(seek)
+                .map(Command::Seek)
+                .ok_or_else(|| usage("seek [+|-]<[m:]ss> | <n>%"))?,
+            "add" if args.is_empty() => return Err(usage("add <query>")),
+            "add" => Command::Add(args.join(" ")),
+            "status" => match args.as_slice() {
+                [] => Command::Status(None),
+                [flag, format] if flag == "-f" || flag == "--format" => {
+                    Command::Status(Some(format.clone()))
+                }
+                [flag] if flag.starts_with("--format=") => {
+                    Command::Status(Some(flag["--format=".len()..].to_string()))
+                }
+                _ => return Err(usage("status [-f <format>]")),
+            },
+            "send" if args.is_empty() => return Err(usage("send <message>")),
+            "send" => Command::Send(args.join(" ")),
+            _ => return Err(format!("unknown command: {}\n\n{}", name, USAGE)),
+        };
+        Ok(command)
+    }
+}
+
+fn seek(arg: &str) -> Option<Seek> {
+    if let Some(percent) = arg.strip_suffix('%') {
+        // Shares are never steps, and `parse` would take a `+`.
+        if !percent.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        return percent
+            .parse()
+            .ok()
+            .filter(|p| *p <= 100)
+            .map(Seek::Percent);
+    }
+    Amount::parse(arg, seconds).map(Seek::Time)
+}
+
+/// Seconds in `ss`, `m:ss` or `h:mm:ss`.
+fn seconds(time: &str) -> Option<i64> {
+    let mut total = 0;
+    for (i, part) in time.split(':').enumerate() {
+        if i > 2 || part.is_empty() || !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        total = total * 60 + part.parse::<i64>().ok()?;
+    }
+    Some(total)
+}
+
+impl Args {
+    pub fn parse<I>(args: I) -> Result<Args, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // Everything from the command on is its own.
+            if !arg.starts_with('-') {
+                parsed.command = Some(Command::parse(&arg, args.collect())?);
+                break;
+            }
+            let (flag, inline) = match arg.find('=') {
+                Some(i) if arg.starts_with("--") => {
+                    (arg[..i].to_string(), Some(arg[i + 1..].to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or(format!("missing value for {}", flag))
+            };
+            match flag.as_str() {
+                "-h" | "--host" => parsed.connection.host = Some(value()?),
+                "-p" | "--port" => {
+                    let port = value()?;
+                    parsed.connection.port = Some(
+                        port.parse()
+                            .map_err(|_| format!("invalid port: {}", port))?,
+                    );
+                }
+                "-P" | "--password" => parsed.connection.password = Some(value()?),
+                "-c" | "--config" => parsed.config = Some(PathBuf::from(value()?)),
+                "--help" => parsed.help = true,
+                _ => return Err(format!("unknown argument: {}\n\n{}", arg, USAGE)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn command(args: &[&str]) -> Result<Command, String> {
+        parse(args).map(|args| args.command.unwrap())
+    }
+
+    #[test]
+    fn options() {
+        let args = parse(&["-h", "pass@host", "--port=6601", "-c", "flux.toml"]).unwrap();
+        assert_eq!(args.connection.host.as_deref(), Some("pass@host"));
+        assert_eq!(args.connection.port, Some(6601));
+        assert_eq!(args.config, Some(PathBuf::from("flux.toml")));
+        assert_eq!(args.command, None);
+        let args = parse(&["--password", "secret", "--help"]).unwrap();
+        assert_eq!(args.connection.password.as_deref(), Some("secret"));
+        assert!(args.help);
+    }
+
+    #[test]
+    fn bad_options() {
+        assert!(parse(&["--nope"])
+            .unwrap_err()
+            .starts_with("unknown argument: --nope"));
+        assert_eq!(parse(&["-p", "lots"]).unwrap_err(), "invalid port: lots");
+        assert_eq!(parse(&["--port=70000"]).unwrap_err(), "invalid port: 70000");
+        assert_eq!(parse(&["-h"]).unwrap_err(), "missing value for -h");
+        // Long options take their value inline; short ones do not.
+        assert!(parse(&["-p=6600"]).is_err());
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(command(&["play"]), Ok(Command::Play(None)));
+        assert_eq!(command(&["play", "3"]), Ok(Command::Play(Some(3))));
+        assert_eq!(command(&["-p", "6600", "next"]), Ok(Command::Next));
+        assert_eq!(command(&["vol"]), Ok(Command::Volume(None)));
+        assert_eq!(
+            command(&["vol", "40"]),
+            Ok(Command::Volume(Some(Amount::To(40))))
+        );
+        assert_eq!(
+            command(&["vol", "-5"]),
+            Ok(Command::Volume(Some(Amount::By(-5))))
+        );
+        assert_eq!(
+            command(&["add", "some", "band"]),
+            Ok(Command::Add("some band".into()))
+        );
+        // Flags after the command are its own.
+        assert_eq!(
+            command(&["status", "-f", "%title%"]),
+            Ok(Command::Status(Some("%title%".into())))
+        );
+        assert_eq!(
+            command(&["status", "--format=%artist%"]),
+            Ok(Command::Status(Some("%artist%".into())))
+        );
+        assert_eq!(
+            command(&["send", "tab", "queue"]),
+            Ok(Command::Send("tab queue".into()))
+        );
+    }
+
+    #[test]
+    fn bad_commands() {
+        assert!(command(&["dance"])
+            .unwrap_err()
+            .starts_with("unknown command: dance"));
+        assert_eq!(
+            command(&["play", "0"]),
+            Err("usage: flux play [<position>]".into())
+        );
+        assert_eq!(
+            command(&["play", "1", "2"]),
+            Err("usage: flux play [<position>]".into())
+        );
+        assert_eq!(command(&["stop", "now"]), Err("usage: flux stop".into()));
+        assert_eq!(
+            command(&["vol", "loud"]),
+            Err("usage: flux vol [[+|-]<percent>]".into())
+        );
+        assert_eq!(command(&["add"]), Err("usage: flux add <query>".into()));
+        assert_eq!(
+            command(&["status", "-f"]),
+            Err("usage: flux status [-f <format>]".into())
+        );
+        assert_eq!(command(&["send"]), Err("usage: flux send <message>".into()));
+    }
+
+    #[test]
+    fn seeks() {
+        let time = |amount| Some(Seek::Time(amount));
+        assert_eq!(seek("30"), time(Amount::To(30)));
+        assert_eq!(seek("1:30"), time(Amount::To(90)));
+        assert_eq!(seek("+1:05"), time(Amount::By(65)));
+        assert_eq!(seek("-10"), time(Amount::By(-10)));
+        assert_eq!(seek("0%"), Some(Seek::Percent(0)));
+        assert_eq!(seek("50%"), Some(Seek::Percent(50)));
+        assert_eq!(seek("100%"), Some(Seek::Percent(100)));
+        assert_eq!(seek("101%"), None);
+        assert_eq!(seek("+5%"), None);
+        assert_eq!(seek("-5%"), None);
+        assert_eq!(seek("%"), None);
+        assert_eq!(seek(""), None);
+        assert_eq!(seek("+"), None);
+        assert_eq!(
+            command(&["seek"]),
+            Err("usage: flux seek [+|-]<[m:]ss> | <n>%".into())
+        );
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(seconds("0"), Some(0));
+        assert_eq!(seconds("75"), Some(75));
+        assert_eq!(seconds("2:05"), Some(125));
+        assert_eq!(seconds("1:00:01"), Some(3601));
+        assert_eq!(seconds("1:2:3:4"), None);
+        assert_eq!(seconds("1::2"), None);
+        assert_eq!(seconds("1:"), None);
+        assert_eq!(seconds("-1"), None);
+        assert_eq!(seconds("1.5"), None);
+    }
+
+    #[test]
+    fn amounts() {
+        assert_eq!(Amount::To(20).from(50), 20);
+        assert_eq!(Amount::By(-20).from(50), 30);
+        assert_eq!(Amount::By(20).from(50), 70);
+    }
+}
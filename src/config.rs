@@ -0,0 +1,221 @@
+use crate::connection::{self, Connection};
+use crate::graphics::Protocol;
+use crate::keys::KeysConfig;
+use crate::libs::ColorScheme;
+use crate::notifications::NotificationsConfig;
+use crate::queue::QueueConfig;
+
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tui::style::Color;
+
+pub const TABS: [&str; 5] = ["now-playing", "library", "playlists", "queue", "settings"];
+
+/// Key binding scope of the options pane, which opens over any tab. Bindings
+/// for it go in `[keys.options]`.
+pub const OPTIONS: usize = TABS.len();
+
+/// Settings read from `$XDG_CONFIG_HOME/flux/config.toml`.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Tab shown at startup, by name or by its number in the tab bar.
+    #[serde(deserialize_with = "tab")]
+    pub tab: usize,
+    /// Milliseconds between redraws while no input arrives.
+    #[serde(deserialize_with = "millis")]
+    pub tick_rate: Duration,
+    /// Whether moving down from the last row of a list goes to the first, and
+    /// up from the first to the last.
+    pub wrap: bool,
+    /// How cover art is drawn: `kitty`, `sixel`, `blocks` of half cells, or
+    /// `auto` for what the terminal supports.
+    pub graphics: Protocol,
+    /// Where the music is, for finding cover art next to the files. Only
+    /// needed when mpd does not say, as it only does over a Unix socket.
+    #[serde(deserialize_with = "directory")]
+    pub music_directory: Option<PathBuf>,
+    /// Where else to look for lyrics, as `<artist> - <title>.lrc` or under
+    /// the song's own file name.
+    #[serde(deserialize_with = "directory")]
+    pub lyrics_directory: Option<PathBuf>,
+    pub connection: connection::Settings,
+    pub colors: ColorScheme,
+    pub queue: QueueConfig,
+    pub keys: KeysConfig,
+    pub notifications: NotificationsConfig,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            tab: 0,
+            tick_rate: Duration::from_millis(250),
+            wrap: false,
+            graphics: Protocol::Auto,
+            music_directory: None,
+            lyrics_directory: None,
+            connection: connection::Settings::default(),
+            colors: ColorScheme::default(),
+            queue: QueueConfig::default(),
+            keys: KeysConfig::default(),
+            notifications: NotificationsConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            Error::Parse(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Config {
+    /// Loads the config from `path`, or from the default location when no path
+    /// is given. A missing default config is not an error.
+    pub fn load(path: Option<&Path>) -> Result<Config, Error> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text).map_err(|err| Error::Parse(path, err)),
+            Err(err) if !required && err.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(err) => Err(Error::Io(path, err)),
+        }
+    }
+}
+
+/// Where to look on this machine for files that go with the songs.
+#[derive(Clone, Debug, Default)]
+pub struct Directories {
+    pub music: Option<PathBuf>,
+    pub lyrics: Option<PathBuf>,
+}
+
+impl Directories {
+    /// The directory holding `file`, a song path as mpd gives it. The music
+    /// directory is asked of mpd when the config does not set it. Streams have
+    /// none.
+    pub fn beside(&self, connection: &Connection, file: &str) -> Option<PathBuf> {
+        if file.contains("://") {
+            return None;
+        }
+        let music = match &self.music {
+            Some(music) => music.clone(),
+            None => connection.music_directory()?,
+        };
+        let dir = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+        Some(music.join(dir))
+    }
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("flux").join("config.toml"))
+}
+
+fn tab<'de, D: Deserializer<'de>>(deserializer: D) -> Result<usize, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Tab {
+        Number(usize),
+        Name(String),
+    }
+    let tab = match Tab::deserialize(deserializer)? {
+        Tab::Number(n) => n.to_string(),
+        Tab::Name(name) => name,
+    };
+    tab_index(&tab).map_err(de::Error::custom)
+}
+
+/// The index of a tab given by name or by its number in the tab bar.
+pub fn tab_index(tab: &str) -> Result<usize, String> {
+    match tab.parse::<usize>() {
+        Ok(n) if (1..=TABS.len()).contains(&n) => Ok(n - 1),
+        Err(_) if TABS.contains(&tab) => Ok(TABS.iter().position(|t| *t == tab).unwrap()),
+        _ => Err(format!(
+            "expected a tab number from 1 to {} or one of {}",
+            TABS.len(),
+            TABS.join(", ")
+        )),
+    }
+}
+
+fn millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    match u64::deserialize(deserializer)? {
+        0 => Err(de::Error::custom("tick_rate must be greater than zero")),
+        ms => Ok(Duration::from_millis(ms)),
+    }
+}
+
+/// Reads a color given by name (`"blue"`, `"lightred"`), as `"#rrggbb"` or as
+/// a 256-color palette index.
+pub fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Spec {
+        Index(u8),
+        Name(String),
+    }
+    let name = match Spec::deserialize(deserializer)? {
+        Spec::Index(i) => return Ok(Color::Indexed(i)),
+        Spec::Name(name) => name,
+    };
+    let color = match name.to_lowercase().replace(['-', '_'], "").as_str() {
+        "reset" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        hex if hex.len() == 7 && hex.starts_with('#') => {
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+            match (channel(1), channel(3), channel(5)) {
+                (Ok(r), Ok(g), Ok(b)) => Color::Rgb(r, g, b),
+                _ => return Err(de::Error::custom(format!("invalid color `{}`", name))),
+            }
+        }
+        _ => return Err(de::Error::custom(format!("invalid color `{}`", name))),
+    };
+    Ok(color)
+}
+
+fn directory<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<PathBuf>, D::Error> {
+    let path = String::deserialize(deserializer)?;
+    Ok(Some(connection::expand_home(&path)))
+}
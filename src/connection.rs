@@ -0,0 +1,437 @@
+use crate::image;
+
+use std::env;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::ops::{Deref, DerefMut};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use mpd::error::{ProtoError, ServerError};
+use serde::Deserialize;
+
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+pub const DEFAULT_PORT: u16 = 6600;
+
+/// The mpd client used throughout flux, talking over either TCP or a Unix
+/// socket. It derefs to the mpd crate's client, and runs the commands that
+/// crate has no call for over the same socket.
+pub struct Client {
+    client: mpd::Client<Stream>,
+    /// The socket of `client`. Every reply the crate reads is read in full,
+    /// so nothing of its waits here between commands.
+    raw: BufReader<Stream>,
+}
+
+impl Client {
+    /// Runs one command the mpd crate has no call for, such as `single
+    /// oneshot`. Returns the `key: value` lines of the reply.
+    pub fn command(&mut self, command: &str) -> mpd::error::Result<Vec<(String, String)>> {
+        exchange(&mut self.raw, command)
+    }
+}
+
+impl Deref for Client {
+    type Target = mpd::Client<Stream>;
+
+    fn deref(&self) -> &mpd::Client<Stream> {
+        &self.client
+    }
+}
+
+impl DerefMut for Client {
+    fn deref_mut(&mut self) -> &mut mpd::Client<Stream> {
+        &mut self.client
+    }
+}
+
+pub enum Stream {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn try_clone(&self) -> io::Result<Stream> {
+        Ok(match self {
+            Stream::Tcp(s) => Stream::Tcp(s.try_clone()?),
+            Stream::Unix(s) => Stream::Unix(s.try_clone()?),
+        })
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.read(buf),
+            Stream::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.write(buf),
+            Stream::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(s) => s.flush(),
+            Stream::Unix(s) => s.flush(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Tcp(String, u16),
+    Unix(PathBuf),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Tcp(host, port) => write!(f, "{}:{}", host, port),
+            Target::Unix(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Connection options as given by one source: the config file, the
+/// environment or the command line. Unset fields fall through to the next source.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub password: Option<String>,
+}
+
+impl Settings {
+    pub fn from_env() -> Settings {
+        Settings {
+            host: env::var("MPD_HOST").ok().filter(|h| !h.is_empty()),
+            port: env::var("MPD_PORT").ok().and_then(|p| p.parse().ok()),
+            password: None,
+        }
+    }
+
+    /// Fills the fields left unset in `self` from `other`.
+    pub fn or(self, other: Settings) -> Settings {
+        Settings {
+            host: self.host.or(other.host),
+            port: self.port.or(other.port),
+            password: self.password.or(other.password),
+        }
+    }
+
+    pub fn connection(&self) -> Connection {
+        let host = self.host.as_deref().unwrap_or(DEFAULT_HOST);
+        let mut connection = Connection::from_host(host, self.port.unwrap_or(DEFAULT_PORT));
+        if self.password.is_some() {
+            connection.password = self.password.clone();
+        }
+        connection
+    }
+}
+
+/// Where to find mpd and how to authenticate once connected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Connection {
+    pub target: Target,
+    pub password: Option<String>,
+}
+
+impl Connection {
+    /// Builds a connection from an `MPD_HOST` style string. The host may carry a
+    /// `password@` prefix and is treated as a Unix socket when it looks like a path.
+    pub fn from_host(host: &str, port: u16) -> Connection {
+        let (password, host) = match host.rfind('@') {
+            Some(0) | None => (None, host),
+            Some(i) => (Some(host[..i].to_string()), &host[i + 1..]),
+        };
+        let target = if host.starts_with('/') || host.starts_with('~') {
+            Target::Unix(expand_home(host))
+        } else {
+            Target::Tcp(host.to_string(), port)
+        };
+        Connection { target, password }
+    }
+
+    fn stream(&self) -> io::Result<Stream> {
+        Ok(match &self.target {
+            Target::Tcp(host, port) => Stream::Tcp(TcpStream::connect((host.as_str(), *port))?),
+            Target::Unix(path) => Stream::Unix(UnixStream::connect(path)?),
+        })
+    }
+
+    pub fn connect(&self) -> mpd::error::Result<Client> {
+        let stream = self.stream()?;
+        let raw = BufReader::new(stream.try_clone()?);
+        let mut client = mpd::Client::new(stream)?;
+        if let Some(password) = &self.password {
+            client.login(password)?;
+        }
+        Ok(Client { client, raw })
+    }
+
+    /// Runs one command over a short-lived connection of its own, for asking
+    /// mpd without a client. Returns the `key: value` lines of the reply.
+    fn command(&self, command: &str) -> mpd::error::Result<Vec<(String, String)>> {
+        exchange(&mut self.open()?, command)
+    }
+
+    /// Reads the picture `command` (`albumart` or `readpicture`) returns for
+    /// `uri`. mpd sends it in chunks, asked for one offset at a time. Returns
+    /// `None` when the song has no picture.
+    pub fn picture(&self, command: &str, uri: &str) -> mpd::error::Result<Option<Vec<u8>>> {
+        let mut stream = self.open()?;
+        let mut picture = vec![];
+        loop {
+            writeln!(
+                stream.get_mut(),
+                "{} {} {}",
+                command,
+                quote(uri),
+                picture.len()
+            )?;
+            let (mut size, mut chunk) = (0, 0);
+            loop {
+                let mut line = String::new();
+                if stream.read_line(&mut line)? == 0 {
+                    return Err(ProtoError::NotOk.into());
+                }
+                let line = line.trim_end_matches('\n');
+                if line == "OK" {
+                    break;
+                }
+                if line.starts_with("ACK ") {
+                    return Err(line.parse::<ServerError>()?.into());
+                }
+                match line.split_once(": ") {
+                    Some(("size", value)) => {
+                        size = value.parse::<usize>()?;
+                        if size > image::MAX_BYTES {
+                            return Err(invalid("picture too large"));
+                        }
+                    }
+                    Some(("binary", value)) => {
+                        chunk = value.parse::<usize>()?;
+                        let start = picture.len();
+                        if chunk > size.saturating_sub(start) {
+                            return Err(invalid("picture larger than its size"));
+                        }
+                        picture.resize(start + chunk, 0);
+                        stream.read_exact(&mut picture[start..])?;
+                        // The data ends with a newline of its own.
+                        stream.read_exact(&mut [0])?;
+                    }
+                    Some(_) => {}
+                    None => return Err(ProtoError::NotPair.into()),
+                }
+            }
+            if chunk == 0 || picture.len() >= size {
+                break;
+            }
+        }
+        Ok(Some(picture).filter(|p| !p.is_empty()))
+    }
+
+    /// The music directory mpd was set up with. It only tells clients on a
+    /// Unix socket.
+    pub fn music_directory(&self) -> Option<PathBuf> {
+        let config = self.command("config").ok()?;
+        let (_, dir) = config.into_iter().find(|(k, _)| k == "music_directory")?;
+        Some(PathBuf::from(dir))
+    }
+
+    /// Connects and logs in, for talking to mpd without the client.
+    fn open(&self) -> mpd::error::Result<BufReader<Stream>> {
+        let mut stream = BufReader::new(self.stream()?);
+        let mut banner = String::new();
+        stream.read_line(&mut banner)?;
+        if !banner.starts_with("OK MPD ") {
+            return Err(ProtoError::BadBanner.into());
+        }
+        if let Some(password) = &self.password {
+            exchange(&mut stream, &format!("password {}", quote(password)))?;
+        }
+        Ok(stream)
+    }
+}
+
+fn invalid(text: &str) -> mpd::error::Error {
+    io::Error::new(io::ErrorKind::InvalidData, text).into()
+}
+
+fn exchange(
+    stream: &mut BufReader<Stream>,
+    command: &str,
+) -> mpd::error::Result<Vec<(String, String)>> {
+    writeln!(stream.get_mut(), "{}", command)?;
+    let mut pairs = vec![];
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line)? == 0 {
+            return Err(ProtoError::NotOk.into());
+        }
+        let line = line.trim_end_matches('\n');
+        if line == "OK" {
+            return Ok(pairs);
+        }
+        if line.starts_with("ACK ") {
+            return Err(line.parse::<ServerError>()?.into());
+        }
+        match line.split_once(": ") {
+            Some((key, value)) => pairs.push((key.to_string(), value.to_string())),
+            None => return Err(ProtoError::NotPair.into()),
+        }
+    }
+}
+
+/// Quotes a command argument for mpd.
+pub fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => {
+            let mut home = PathBuf::from(home);
+            home.push(rest.trim_start_matches('/'));
+            home
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Returns true when `err` means the connection itself is gone, as opposed to
+/// mpd refusing a single command.
+pub fn is_disconnect(err: &mpd::error::Error) -> bool {
+    matches!(err, mpd::error::Error::Io(_) | mpd::error::Error::Proto(_))
+}
+
+const MIN_RETRY: Duration = Duration::from_millis(500);
+const MAX_RETRY: Duration = Duration::from_secs(30);
+
+/// Tracks a lost connection and when to try reaching the server again. The
+/// delay between attempts doubles after every failure, up to `MAX_RETRY`.
+pub struct Offline {
+    pub target: String,
+    pub error: String,
+    pub attempts: u32,
+    delay: Duration,
+    retry_at: Instant,
+}
+
+impl Offline {
+    pub fn new(target: &Target, error: &mpd::error::Error) -> Offline {
+        Offline {
+            target: target.to_string(),
+            error: error.to_string(),
+            attempts: 0,
+            delay: MIN_RETRY,
+            retry_at: Instant::now() + MIN_RETRY,
+        }
+    }
+
+    pub fn should_retry(&self) -> bool {
+        Instant::now() >= self.retry_at
+    }
+
+    pub fn retry_in(&self) -> Duration {
+        self.retry_at.saturating_duration_since(Instant::now())
+    }
+
+    pub fn failed(&mut self, error: &mpd::error::Error) {
+        self.error = error.to_string();
+        self.attempts += 1;
+        self.delay = (self.delay * 2).min(MAX_RETRY);
+        self.retry_at = Instant::now() + self.delay;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::os::unix::net::UnixListener;
+    use std::process;
+    use std::thread;
+
+    /// A server at a socket of its own that greets, then answers every line
+    /// with the next of `replies`.
+    fn serve(name: &str, replies: Vec<Vec<u8>>) -> Connection {
+        let path = env::temp_dir().join(format!("flux-{}-{}.sock", name, process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let bound = path.clone();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let _ = std::fs::remove_file(bound);
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            writer.write_all(b"OK MPD 0.23.0\n").unwrap();
+            for reply in replies {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                    return;
+                }
+                if writer.write_all(&reply).is_err() {
+                    return;
+                }
+            }
+        });
+        Connection {
+            target: Target::Unix(path),
+            password: None,
+        }
+    }
+
+    #[test]
+    fn picture_in_chunks() {
+        let connection = serve(
+            "chunks",
+            vec![
+                b"size: 5\nbinary: 3\nabc\nOK\n".to_vec(),
+                b"size: 5\nbinary: 2\nde\nOK\n".to_vec(),
+            ],
+        );
+        let picture = connection.picture("albumart", "a.flac").unwrap();
+        assert_eq!(picture, Some(b"abcde".to_vec()));
+    }
+
+    #[test]
+    fn picture_too_large() {
+        let connection = serve("large", vec![b"size: 4000000000\nbinary: 1\n".to_vec()]);
+        assert!(connection.picture("albumart", "a.flac").is_err());
+    }
+
+    #[test]
+    fn chunk_past_size() {
+        let connection = serve("past", vec![b"size: 2\nbinary: 3000000000\n".to_vec()]);
+        assert!(connection.picture("albumart", "a.flac").is_err());
+    }
+
+    #[test]
+    fn command_between_calls() {
+        let connection = serve(
+            "raw",
+            vec![
+                b"OK\n".to_vec(),
+                b"single: oneshot\nmixrampdb: -17\nOK\n".to_vec(),
+                b"OK\n".to_vec(),
+            ],
+        );
+        let mut client = connection.connect().unwrap();
+        client.ping().unwrap();
+        let pairs = client.command("status").unwrap();
+        assert_eq!(pairs[0], ("single".into(), "oneshot".into()));
+        assert_eq!(pairs.len(), 2);
+        client.ping().unwrap();
+    }
+}
@@ -0,0 +1,300 @@
+use crate::cli::{Command, Seek};
+use crate::connection::{Client, Connection};
+use crate::ipc;
+use crate::modes::{Modes, Single};
+use crate::search;
+
+use mpd::status::{State, Status};
+use mpd::Song;
+use std::error::Error;
+
+/// Exit statuses, as listed in the usage.
+pub const FAILED: i32 = 1;
+pub const USAGE: i32 = 2;
+pub const UNREACHABLE: i32 = 3;
+
+/// Runs `command` on its own connection and returns the exit status.
+pub fn run(command: Command, connection: &Connection) -> i32 {
+    // Messages go to the running flux rather than to mpd.
+    if let Command::Send(message) = &command {
+        return ipc::send(message);
+    }
+    let mut client = match connection.connect() {
+        Ok(client) => client,
+        Err(err) => {
+            eprintln!("flux: could not connect to {}: {}", connection.target, err);
+            return UNREACHABLE;
+        }
+    };
+    match execute(command, &mut client) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("flux: {}", err);
+            FAILED
+        }
+    }
+}
+
+fn execute(command: Command, client: &mut Client) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Play(None) => client.play()?,
+        // Positions count from 1 on the command line, as in the queue tab.
+        Command::Play(Some(position)) => client.switch(position - 1)?,
+        Command::Pause => client.pause(true)?,
+        Command::Toggle => {
+            if client.status()?.state == State::Play {
+                client.pause(true)?
+            } else {
+                client.play()?
+            }
+        }
+        Command::Stop => client.stop()?,
+        Command::Next => client.next()?,
+        Command::Prev => client.prev()?,
+        Command::Volume(amount) => {
+            let volume = client.status()?.volume;
+            if volume < 0 {
+                return Err("mpd has no volume control".into());
+            }
+            let volume = match amount {
+                Some(amount) => {
+                    let volume = amount.from(volume as i64).clamp(0, 100) as i8;
+                    client.volume(volume)?;
+                    volume
+                }
+                None => volume,
+            };
+            println!("volume: {}%", volume);
+        }
+        Command::Seek(seek) => {
+            let status = client.status()?;
+            let (elapsed, duration) = match (status.elapsed, status.duration) {
+                (Some(elapsed), Some(duration)) if status.song.is_some() => {
+                    (elapsed.num_seconds(), duration.num_seconds())
+                }
+                _ => return Err("nothing is playing".into()),
+            };
+            let second = match seek {
+                Seek::Time(amount) => amount.from(elapsed),
+                Seek::Percent(percent) => duration * percent as i64 / 100,
+            };
+            client.rewind(second.clamp(0, duration))?;
+        }
+        Command::Add(query) => {
+            let songs = search::matching(client, &query, &|| Ok(()))?;
+            if songs.is_empty() {
+                return Err(format!("no songs match {}", query).into());
+            }
+            for song in &songs {
+                client.push(song)?;
+            }
+            match songs.len() {
+                1 => println!("added 1 song"),
+                n => println!("added {} songs", n),
+            }
+        }
+        Command::Send(_) => {}
+        Command::Status(format) => {
+            let status = client.status()?;
+            let song = client.currentsong()?;
+            match format {
+                Some(format) => println!("{}", fill(&format, song.as_ref(), &status)),
+                None => print!(
+                    "{}",
+                    summary(song.as_ref(), &status, &Modes::fetch(client)?)
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The status in the shape mpc prints it.
+fn summary(song: Option<&Song>, status: &Status, modes: &Modes) -> String {
+    let mut out = String::new();
+    if let Some(song) = song {
+        let title = match song.title {
+            Some(_) => fill("%artist% - %title%", Some(song), status),
+            None => song.file.clone(),
+        };
+        out.push_str(&format!("{}\n", title));
+        out.push_str(&fill(
+            "[%state%] #%position%/%length%   %elapsed%/%time% (%percent%%)\n",
+            Some(song),
+            status,
+        ));
+    }
+    let on = |on: bool| if on { "on" } else { "off" };
+    let single = match modes.single {
+        Single::Off => "off",
+        Single::On => "on",
+        Single::Oneshot => "oneshot",
+    };
+    let volume = match status.volume {
+        v if v < 0 => String::from("n/a"),
+        v => format!("{}%", v),
+    };
+    out.push_str(&format!(
+        "volume: {}   repeat: {}   random: {}   single: {}   consume: {}\n",
+        volume,
+        on(status.repeat),
+        on(status.random),
+        single,
+        on(status.consume)
+    ));
+    out
+}
+
+/// Fills the `%name%` fields of `format` in: any tag of the song, or one of
+/// `elapsed`, `time`, `percent`, `state`, `volume`, `position` and `length`.
+/// Unknown fields come out empty. `%%` is a percent sign, and `\n` and `\t`
+/// a new line and a tab.
+pub fn fill(format: &str, song: Option<&Song>, status: &Status) -> String {
+    let mut out = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => {
+                let rest = chars.as_str();
+                let end = match rest.find('%') {
+                    Some(end) => end,
+                    None => {
+                        out.push('%');
+                        continue;
+                    }
+                };
+                match &rest[..end] {
+                    "" => out.push('%'),
+                    name => out.push_str(&field(name, song, status)),
+                }
+                chars = rest[end + 1..].chars();
+            }
+            '\\' => match chars.clone().next() {
+                Some('n') => {
+                    out.push('\n');
+                    chars.next();
+                }
+                Some('t') => {
+                    out.push('\t');
+                    chars.next();
+                }
+                _ => out.push('\\'),
+            },
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn field(name: &str, song: Option<&Song>, status: &Status) -> String {
+    let clock = |d: Option<time::Duration>| match d {
+        Some(d) => format!("{:0>2}:{:0>2}", d.num_minutes(), d.num_seconds() % 60),
+        None => String::new(),
+    };
+    match name.to_lowercase().as_str() {
+        "elapsed" => clock(status.elapsed),
+        "time" => clock(status.duration),
+        "percent" => match (status.elapsed, status.duration) {
+            (Some(elapsed), Some(duration)) if duration.num_milliseconds() > 0 => {
+                (elapsed.num_milliseconds() * 100 / duration.num_milliseconds()).to_string()
+            }
+            _ => String::from("0"),
+        },
+        "state" => String::from(match status.state {
+            State::Play => "playing",
+            State::Pause => "paused",
+            State::Stop => "stopped",
+        }),
+        "volume" => status.volume.to_string(),
+        "position" => status
+            .song
+            .map_or(String::new(), |place| (place.pos + 1).to_string()),
+        "length" => status.queue_len.to_string(),
+        tag => {
+            let song = match song {
+                Some(song) => song,
+                None => return String::new(),
+            };
+            match tag {
+                "title" => song.title.clone().unwrap_or_default(),
+                "name" => song.name.clone().unwrap_or_default(),
+                "file" => song.file.clone(),
+                _ => song
+                    .tags
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(tag))
+                    .map_or(String::new(), |(_, value)| value.clone()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mpd::song::QueuePlace;
+    use mpd::Id;
+
+    fn song() -> Song {
+        let mut song = Song {
+            file: "a/b.flac".into(),
+            title: Some("A Song".into()),
+            ..Song::default()
+        };
+        song.tags.insert("Artist".into(), "A Band".into());
+        song
+    }
+
+    fn status() -> Status {
+        Status {
+            volume: 70,
+            queue_len: 12,
+            state: State::Play,
+            song: Some(QueuePlace {
+                id: Id(7),
+                pos: 2,
+                prio: 0,
+            }),
+            elapsed: Some(time::Duration::seconds(65)),
+            duration: Some(time::Duration::seconds(260)),
+            ..Status::default()
+        }
+    }
+
+    #[test]
+    fn fields() {
+        let (song, status) = (song(), status());
+        let fill = |format: &str| fill(format, Some(&song), &status);
+        assert_eq!(fill("%artist% - %title%"), "A Band - A Song");
+        assert_eq!(fill("%ARTIST% %File%"), "A Band a/b.flac");
+        assert_eq!(
+            fill("%elapsed%/%time% %percent% %state%"),
+            "01:05/04:20 25 playing"
+        );
+        assert_eq!(fill("%volume% %position%/%length%"), "70 3/12");
+    }
+
+    #[test]
+    fn missing_fields() {
+        let status = status();
+        // Unknown fields and tags the song lacks come out empty.
+        assert_eq!(fill("[%nope%] [%album%]", Some(&song()), &status), "[] []");
+        // So does every tag with nothing playing, but not the status.
+        assert_eq!(fill("%title%|%volume%", None, &status), "|70");
+        assert_eq!(fill("%percent%", None, &Status::default()), "0");
+        assert_eq!(fill("%position%", None, &Status::default()), "");
+    }
+
+    #[test]
+    fn escapes() {
+        let (song, status) = (song(), status());
+        let fill = |format: &str| fill(format, Some(&song), &status);
+        assert_eq!(fill("%percent%%%"), "25%");
+        assert_eq!(fill("100%% %%title%%"), "100% %title%");
+        // A percent sign without a closing one is kept as it is.
+        assert_eq!(fill("50%% %title"), "50% %title");
+        assert_eq!(fill("%title% 50%"), "A Song 50%");
+        assert_eq!(fill("%title%\\n%artist%\\t!"), "A Song\nA Band\t!");
+        assert_eq!(fill("C:\\music\\"), "C:\\music\\");
+    }
+}
@@ -0,0 +1,258 @@
+use crate::connection::Client;
+use crate::finder::Finder;
+use crate::info;
+use crate::keys::Action;
+use crate::library::{LibItem, LibKind, SongRef};
+use crate::libs::{self, update_queue, Data};
+use crate::menu::Menu;
+use crate::modes;
+use crate::playlists;
+use crate::queue;
+use crate::search::{self, Search};
+use crate::seek;
+
+use mpd::{error::Result, status};
+
+/// Runs `action` against the current state and the server. Quitting and help,
+/// which needs the keymap, are left to the main loop.
+pub fn run(action: Action, data: &mut Data, client: &mut Client) -> Result<()> {
+    counted(action, None, data, client)
+}
+
+/// Runs `action` with the count typed before it. Only moves take a count.
+pub fn counted(
+    action: Action,
+    count: Option<usize>,
+    data: &mut Data,
+    client: &mut Client,
+) -> Result<()> {
+    if let Some(motion) = action.motion(count) {
+        data.motion(motion);
+        return Ok(());
+    }
+    match action {
+        Action::Quit | Action::Help => {}
+        // Moves went to `Data::motion` above.
+        Action::Down
+        | Action::Up
+        | Action::Top
+        | Action::Bottom
+        | Action::PageDown
+        | Action::PageUp
+        | Action::HalfPageDown
+        | Action::HalfPageUp
+        | Action::ScreenTop
+        | Action::ScreenMiddle
+        | Action::ScreenBottom => {}
+        Action::ToggleOptions => {
+            if data.options {
+                data.options = false
+            } else {
+                data.options = true
+            }
+        }
+        Action::NowPlaying => data.tabindex = 0,
+        Action::Library => data.tabindex = 1,
+        Action::Playlists => data.tabindex = 2,
+        Action::Queue => data.tabindex = 3,
+        Action::Settings => data.tabindex = 4,
+        Action::Stop => {
+            client.stop()?;
+        }
+        Action::VolumeUp => {
+            if data.status.volume < 99 {
+                let vol = &data.status.volume + 2;
+                client.volume(vol)?;
+            }
+        }
+        Action::VolumeDown => {
+            if data.status.volume > 1 {
+                let vol = &data.status.volume - 2;
+                client.volume(vol)?;
+            }
+        }
+        Action::TogglePause => {
+            let status = client.status()?;
+            if status.state == status::State::Play {
+                client.pause(true)?
+            } else {
+                client.play()?;
+            }
+        }
+        Action::UpdateDatabase => {
+            // The library is reloaded once the idle watcher reports the
+            // database changed.
+            client.update()?;
+        }
+        Action::Delete => {
+            if data.tabindex == 3 {
+                queue::delete(data, client)?;
+            } else if data.tabindex == 2 {
+                playlists::delete(data, client)?;
+            }
+        }
+        Action::NextTab => data.nexttab(),
+        Action::PrevTab => data.prevtab(),
+        Action::AddToQueue => {
+            if data.tabindex == 2 {
+                playlists::add_to_queue(data, client)?
+            } else {
+                data.library.add_to_queue(data, client)?
+            }
+        }
+        Action::Search => Search::open(data),
+        Action::NextMatch => search::jump(data, true),
+        Action::PrevMatch => search::jump(data, false),
+        Action::Finder => Finder::open(data),
+        Action::Visual => queue::visual(data),
+        Action::Mark => queue::mark(data),
+        Action::ClearSelection => data.selection = queue::Selection::default(),
+        Action::MoveUp | Action::MoveDown => {
+            let up = action == Action::MoveUp;
+            if data.tabindex == 2 {
+                playlists::shift(data, client, up)?
+            } else {
+                queue::shift(data, client, up)?
+            }
+        }
+        Action::ClearQueue => queue::clear(data, client)?,
+        Action::Crop => queue::crop(data, client)?,
+        Action::Shuffle => queue::shuffle(data, client)?,
+        Action::LoadPlaylist => playlists::load(data, client, true)?,
+        Action::SaveQueue => playlists::ask_save(data, client)?,
+        Action::Rename => playlists::ask_rename(data),
+        Action::AddToPlaylist => {
+            let songs = data.library.songs(client)?;
+            playlists::ask_add(data, client, songs)?
+        }
+        Action::SeekForward => seek::by(data, client, seek::SHORT)?,
+        Action::SeekBackward => seek::by(data, client, -seek::SHORT)?,
+        Action::SeekForwardLong => seek::by(data, client, seek::LONG)?,
+        Action::SeekBackwardLong => seek::by(data, client, -seek::LONG)?,
+        Action::SeekTo(percent) => seek::to(data, client, percent)?,
+        Action::ToggleRepeat => modes::toggle_repeat(data, client)?,
+        Action::ToggleRandom => modes::toggle_random(data, client)?,
+        Action::ToggleSingle => modes::cycle_single(data, client)?,
+        Action::ToggleConsume => modes::toggle_consume(data, client)?,
+        Action::ToggleCrossfade => modes::toggle_crossfade(data, client)?,
+        Action::Menu => Menu::open(data, client)?,
+        Action::Info => info::open(data, client)?,
+        Action::Increase | Action::Decrease => {
+            if data.options {
+                modes::step(data, client, action == Action::Increase)?
+            }
+        }
+        Action::NextSong => {
+            client.next()?;
+            client.pause(true)?;
+            client.play()?
+        }
+        Action::PrevSong => {
+            client.prev()?;
+            client.pause(true)?;
+            client.play()?
+        }
+        Action::Enter => {
+            let idx = data.library.state.selected().unwrap_or(0);
+
+            if data.options {
+                return modes::enter(data, client);
+            }
+
+            if data.tabindex == 4 {
+                let selected = data.settings.state.selected();
+                let item = selected.and_then(|i| data.settings.items.get(i));
+                if item.map(|i| i.trim()) == Some("Search") {
+                    Search::open(data);
+                }
+                return Ok(());
+            }
+            if data.tabindex == 2 {
+                return playlists::enter(data, client);
+            }
+            if data.tabindex == 3 {
+                if let Some(SongRef::Queue { id, .. }) = queue_selected(data) {
+                    client.switch(id)?;
+                    client.pause(true)?;
+                    client.play()?;
+                }
+            }
+            if data.tabindex == 1 && idx < data.library.items.len() {
+                if data.library.items[idx].tag == LibKind::Artist {
+                    open_artist(data, client)?;
+                } else if data.library.items[idx].tag == LibKind::Album {
+                    open_album(data, client)?;
+                } else if data.library.items[idx].tag == LibKind::Title {
+                    if let SongRef::File(file) = &data.library.items[idx].song {
+                        let id = client.push(libs::song(file))?;
+                        update_queue(data, client)?;
+                        client.switch(id)?;
+                        client.pause(true)?;
+                        client.play()?;
+                    }
+                } else if data.library.items[idx].tag == LibKind::Back {
+                    match data.library.items.get(idx + 1).map(|i| i.tag) {
+                        Some(LibKind::Artist) | None => {
+                            data.library = data.artists.clone();
+                            data.albums.state.select(Some(0))
+                        }
+                        Some(LibKind::Album) => data.library = data.albums.clone(),
+                        _ => {}
+                    }
+                };
+            };
+        }
+    }
+    Ok(())
+}
+
+/// Lists the albums of the artist under the cursor in the library tab.
+pub fn open_artist(data: &mut Data, client: &mut Client) -> Result<()> {
+    let pos = data.library.state.selected().unwrap();
+    data.artists.state.select(Some(pos));
+    let item = data.library.get_albums(client)?;
+    data.albums.items.clear();
+    data.library.state.select(Some(0));
+    let mut temp = vec![
+        LibItem::new("  [Back]".into(), LibKind::Back),
+        LibItem::new(
+            data.library.items[pos].content.clone().into(),
+            LibKind::Artist,
+        ),
+    ];
+    data.albums.items.append(&mut temp);
+    for (i, album) in item.items.iter().enumerate() {
+        data.albums
+            .items
+            .insert(i + 2, LibItem::new(album.content.clone(), LibKind::Album));
+        data.library = data.albums.clone();
+    }
+    Ok(())
+}
+
+/// Lists the songs of the album under the cursor in the library tab.
+pub fn open_album(data: &mut Data, client: &mut Client) -> Result<()> {
+    let pos = data.library.state.selected().unwrap();
+    data.albums.state.select(Some(pos));
+    let item = data.library.get_titles(client)?;
+    data.titles.items.clear();
+    data.library.state.select(Some(0));
+    let mut temp = vec![
+        LibItem::new("  [Back]".into(), LibKind::Back),
+        LibItem::new(
+            data.library.items[pos].content.clone().into(),
+            LibKind::Album,
+        ),
+    ];
+    data.titles.items.append(&mut temp);
+    for (i, title) in item.items.iter().enumerate() {
+        data.titles.items.insert(i + 2, title.clone());
+    }
+    data.library = data.titles.clone();
+    Ok(())
+}
+
+fn queue_selected(data: &Data) -> Option<SongRef> {
+    let selected = data.queue.state.selected()?;
+    data.queue.items.get(selected).map(|item| item.song.clone())
+}
@@ -1,4 +1,4 @@
-use std::io;
+use std::io::{self, Read};
 use std::sync::mpsc;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -7,18 +7,40 @@ use std::sync::{
 use std::thread;
 use std::time::Duration;
 
-use termion::event::Key;
+use crate::graphics;
+use crate::ipc::{self, Message};
+#[cfg(feature = "mpris")]
+use crate::mpris;
+use crate::worker::{Kind, Reply};
+
+use mpd::idle::Subsystem;
+use termion::event::{self, Key, MouseEvent};
 use termion::input::TermRead;
 
 pub enum Event<I> {
     Input(I),
+    Mouse(MouseEvent),
     Tick,
+    /// Something changed on the server.
+    Changed(Subsystem),
+    /// The connection watching the server for changes was lost.
+    Lost,
+    /// The worker finished a request.
+    Done(Kind, u64, mpd::error::Result<Reply>),
+    /// A message came in on the control socket.
+    Remote(Message, ipc::Reply),
+    /// The terminal answered the device attributes query.
+    Attributes(Vec<u16>),
+    /// A client of the MPRIS interface asked for something.
+    #[cfg(feature = "mpris")]
+    Mpris(mpris::Command),
 }
 
 /// A small event handler that wrap termion input and tick events. Each event
 /// type is handled in its own thread and returned to a common `Receiver`
 pub struct Events {
     rx: mpsc::Receiver<Event<Key>>,
+    tx: mpsc::Sender<Event<Key>>,
     input_handle: thread::JoinHandle<()>,
     ignore_exit_key: Arc<AtomicBool>,
     tick_handle: thread::JoinHandle<()>,
@@ -40,10 +62,6 @@ impl Default for Config {
 }
 
 impl Events {
-    pub fn new() -> Events {
-        Events::with_config(Config::default())
-    }
-
     pub fn with_config(config: Config) -> Events {
         let (tx, rx) = mpsc::channel();
         let ignore_exit_key = Arc::new(AtomicBool::new(false));
@@ -51,21 +69,30 @@ impl Events {
             let tx = tx.clone();
             let ignore_exit_key = ignore_exit_key.clone();
             thread::spawn(move || {
-                let stdin = io::stdin();
-                for evt in stdin.keys() {
-                    if let Ok(key) = evt {
-                        if let Err(err) = tx.send(Event::Input(key)) {
-                            eprintln!("{}", err);
-                            return;
-                        }
-                        if !ignore_exit_key.load(Ordering::Relaxed) && key == config.exit_key {
-                            return;
-                        }
+                let stdin = Answers {
+                    inner: io::stdin(),
+                    buf: Vec::new(),
+                    pos: 0,
+                    tx: tx.clone(),
+                };
+                for evt in stdin.events() {
+                    let (event, key) = match evt {
+                        Ok(event::Event::Key(key)) => (Event::Input(key), Some(key)),
+                        Ok(event::Event::Mouse(mouse)) => (Event::Mouse(mouse), None),
+                        _ => continue,
+                    };
+                    if let Err(err) = tx.send(event) {
+                        eprintln!("{}", err);
+                        return;
+                    }
+                    if !ignore_exit_key.load(Ordering::Relaxed) && key == Some(config.exit_key) {
+                        return;
                     }
                 }
             })
         };
         let tick_handle = {
+            let tx = tx.clone();
             thread::spawn(move || loop {
                 if tx.send(Event::Tick).is_err() {
                     break;
@@ -75,6 +102,7 @@ impl Events {
         };
         Events {
             rx,
+            tx,
             ignore_exit_key,
             input_handle,
             tick_handle,
@@ -85,6 +113,11 @@ impl Events {
         self.rx.recv()
     }
 
+    /// A handle for other threads to send events through.
+    pub fn sender(&self) -> mpsc::Sender<Event<Key>> {
+        self.tx.clone()
+    }
+
     pub fn disable_exit_key(&mut self) {
         self.ignore_exit_key.store(true, Ordering::Relaxed);
     }
@@ -93,3 +126,47 @@ impl Events {
         self.ignore_exit_key.store(false, Ordering::Relaxed);
     }
 }
+
+/// Stdin with the terminal's answers to the device attributes query taken
+/// out and sent on as `Event::Attributes`, as termion cannot parse them and
+/// would pass them on as keys.
+struct Answers<R> {
+    inner: R,
+    /// What was read last, answers taken out, and how much of it was handed on.
+    buf: Vec<u8>,
+    pos: usize,
+    tx: mpsc::Sender<Event<Key>>,
+}
+
+impl<R: Read> Read for Answers<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        // termion reads a byte or two at a time, and an answer comes in one
+        // piece, so the filtering is done on reads of our own.
+        while self.pos == self.buf.len() {
+            let mut chunk = [0; 1024];
+            let n = self.inner.read(&mut chunk)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            self.buf.clear();
+            self.pos = 0;
+            let mut i = 0;
+            while i < n {
+                match graphics::attributes(&chunk[i..n]) {
+                    Some((len, attributes)) => {
+                        let _ = self.tx.send(Event::Attributes(attributes));
+                        i += len;
+                    }
+                    None => {
+                        self.buf.push(chunk[i]);
+                        i += 1;
+                    }
+                }
+            }
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
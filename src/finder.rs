@@ -0,0 +1,187 @@
+use crate::connection::Client;
+use crate::fuzzy;
+use crate::keys;
+use crate::library::{LibItem, LibKind};
+use crate::libs::{Data, Library};
+use crate::worker::{Check, Request};
+
+use mpd::{error::Result, Query, Song, Term};
+use termion::event::Key;
+
+/// Most results kept after ranking, more than fit on any screen.
+const MAX_RESULTS: usize = 500;
+
+/// Songs asked for at a time while indexing, so a stale index stops early.
+const CHUNK: u32 = 5000;
+
+pub struct Entry {
+    song: Song,
+    display: String,
+    /// `display` lowercased char by char, matched against the query.
+    chars: Vec<char>,
+    path: Vec<char>,
+}
+
+/// Every song in the database, ready to be matched against.
+pub struct Index(Vec<Entry>);
+
+/// The Ctrl-P popup: fuzzy search over every song in the database.
+///
+/// The index is loaded in the background the first time the finder opens and
+/// kept until the database is reloaded.
+pub struct Finder {
+    pub open: bool,
+    pub query: String,
+    pub results: Library,
+    /// Whether the index is still being loaded.
+    pub loading: bool,
+    index: Vec<Entry>,
+    /// Index entry behind each row of `results`.
+    rows: Vec<usize>,
+}
+
+impl Finder {
+    pub fn open(data: &mut Data) {
+        if data.finder.is_none() {
+            data.finder = Some(Finder {
+                open: false,
+                query: String::new(),
+                results: Library::newlib(vec![]),
+                loading: true,
+                index: vec![],
+                rows: vec![],
+            });
+            data.requests.push(Request::Index);
+        }
+        if let Some(finder) = data.finder.as_mut() {
+            finder.open = true;
+            finder.query.clear();
+            finder.rank();
+        }
+    }
+
+    /// Takes the index once it arrives, keeping whatever was typed meanwhile.
+    pub fn loaded(data: &mut Data, index: Index) {
+        if let Some(finder) = data.finder.as_mut() {
+            finder.index = index.0;
+            finder.loading = false;
+            finder.rank();
+        }
+    }
+
+    /// Drops a finder whose index could not be loaded, so opening it again
+    /// asks anew.
+    pub fn failed(data: &mut Data) {
+        data.finder = None;
+    }
+
+    fn rank(&mut self) {
+        let mut scored: Vec<(i64, usize, Vec<usize>)> = vec![];
+        for (i, entry) in self.index.iter().enumerate() {
+            if self.query.trim().is_empty() {
+                scored.push((0, i, vec![]));
+            } else if let Some((score, matches)) = fuzzy::score(&self.query, &entry.chars) {
+                scored.push((score, i, matches));
+            } else if let Some((score, _)) = fuzzy::score(&self.query, &entry.path) {
+                // Matched on the file path only, which is not displayed.
+                scored.push((score / 2, i, vec![]));
+            }
+        }
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        scored.truncate(MAX_RESULTS);
+
+        self.rows = scored.iter().map(|(_, i, _)| *i).collect();
+        self.results = Library::newlib(
+            scored
+                .into_iter()
+                .map(|(_, i, matches)| {
+                    let mut item = LibItem::new(self.index[i].display.clone(), LibKind::None);
+                    item.matches = matches;
+                    item
+                })
+                .collect(),
+        );
+    }
+
+    fn selected(&self) -> Option<&Song> {
+        let row = self.results.state.selected()?;
+        self.rows.get(row).map(|&i| &self.index[i].song)
+    }
+}
+
+/// Feeds a key typed while the finder is open. Enter plays the selected song
+/// right away, Tab appends it to the queue and keeps the finder open.
+pub fn input(key: Key, data: &mut Data, client: &mut Client) -> Result<()> {
+    let wrap = data.wrap;
+    let finder = match data.finder.as_mut() {
+        Some(finder) => finder,
+        None => return Ok(()),
+    };
+    match key {
+        Key::Esc | Key::Ctrl('c') => finder.open = false,
+        Key::Char('\n') => {
+            if let Some(song) = finder.selected() {
+                let id = client.push(song)?;
+                client.switch(id)?;
+            }
+            finder.open = false;
+        }
+        Key::Char('\t') => {
+            if let Some(song) = finder.selected() {
+                client.push(song)?;
+            }
+        }
+        Key::Backspace => {
+            finder.query.pop();
+            finder.rank();
+        }
+        Key::Char(c) => {
+            finder.query.push(c);
+            finder.rank();
+        }
+        _ => {
+            if let Some(motion) = keys::popup_motion(key) {
+                finder.results.motion(motion, wrap);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn index(client: &mut Client, check: Check) -> Result<Index> {
+    // The client has no listallinfo; searching for an empty file name matches
+    // every song and returns the same information.
+    let mut query = Query::new();
+    let query = query.and(Term::File, "");
+    let mut songs = vec![];
+    loop {
+        check()?;
+        let start = songs.len() as u32;
+        let chunk = client.search(query, (start, start + CHUNK))?;
+        let done = (chunk.len() as u32) < CHUNK;
+        songs.extend(chunk);
+        if done {
+            break;
+        }
+    }
+    Ok(Index(
+        songs
+            .into_iter()
+            .map(|song| {
+                let tag = |name: &str| song.tags.get(name).cloned().unwrap_or_default();
+                let title = song.title.clone().unwrap_or_default();
+                let display = if title.is_empty() {
+                    song.file.clone()
+                } else {
+                    format!("{} - {} - {}", tag("Artist"), tag("Album"), title)
+                };
+                Entry {
+                    chars: display.chars().map(fuzzy::fold).collect(),
+                    path: song.file.chars().map(fuzzy::fold).collect(),
+                    display,
+                    song,
+                }
+            })
+            .collect(),
+    ))
+}
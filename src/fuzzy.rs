@@ -0,0 +1,153 @@
+/// Fuzzy matching in the style of fzf: every whitespace separated term of the
+/// pattern must appear in the candidate as a case-insensitive subsequence.
+///
+/// Returns the score (higher is better) and the positions, in chars, of the
+/// matched characters so they can be highlighted.
+pub fn score(pattern: &str, candidate: &[char]) -> Option<(i64, Vec<usize>)> {
+    let mut total = 0;
+    let mut positions = vec![];
+    for term in pattern.split_whitespace() {
+        let term: Vec<char> = term.chars().map(fold).collect();
+        let (score, matched) = score_term(&term, candidate)?;
+        total += score;
+        positions.extend(matched);
+    }
+    positions.sort_unstable();
+    positions.dedup();
+    Some((total, positions))
+}
+
+/// Lowercases a char while keeping a one to one mapping between positions.
+pub fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+const MATCH: i64 = 16;
+const CONSECUTIVE: i64 = 8;
+const BOUNDARY: i64 = 10;
+const GAP: i64 = 1;
+
+/// Tries every occurrence of the term's first char as a starting point and
+/// greedily matches the rest, keeping the best scoring alignment.
+fn score_term(term: &[char], candidate: &[char]) -> Option<(i64, Vec<usize>)> {
+    let first = *term.first()?;
+    let mut best: Option<(i64, Vec<usize>)> = None;
+    for start in (0..candidate.len()).filter(|&i| fold(candidate[i]) == first) {
+        let mut positions = vec![start];
+        let mut next = start + 1;
+        for &c in &term[1..] {
+            match (next..candidate.len()).find(|&i| fold(candidate[i]) == c) {
+                Some(i) => {
+                    positions.push(i);
+                    next = i + 1;
+                }
+                None => break,
+            }
+        }
+        if positions.len() < term.len() {
+            // Later starting points only leave less room for the rest.
+            break;
+        }
+        let score = rate(&positions, candidate);
+        if best.as_ref().is_none_or(|(b, _)| score > *b) {
+            best = Some((score, positions));
+        }
+    }
+    best
+}
+
+fn rate(positions: &[usize], candidate: &[char]) -> i64 {
+    let mut score = 0;
+    for (n, &i) in positions.iter().enumerate() {
+        score += MATCH;
+        if i == 0 || !candidate[i - 1].is_alphanumeric() {
+            score += BOUNDARY;
+        }
+        if n > 0 {
+            let gap = (i - positions[n - 1] - 1) as i64;
+            if gap == 0 {
+                score += CONSECUTIVE;
+            } else {
+                score -= GAP * gap.min(16);
+            }
+        }
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score_of(pattern: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+        let candidate: Vec<char> = candidate.chars().collect();
+        score(pattern, &candidate)
+    }
+
+    fn positions(pattern: &str, candidate: &str) -> Option<Vec<usize>> {
+        score_of(pattern, candidate).map(|(_, positions)| positions)
+    }
+
+    fn points(pattern: &str, candidate: &str) -> i64 {
+        score_of(pattern, candidate).unwrap().0
+    }
+
+    #[test]
+    fn empty_pattern() {
+        assert_eq!(score_of("", "anything"), Some((0, vec![])));
+        assert_eq!(score_of("  ", ""), Some((0, vec![])));
+        assert_eq!(score_of("a", ""), None);
+    }
+
+    #[test]
+    fn terms() {
+        // Every term has to match, in any order, and the positions add up.
+        assert_eq!(
+            positions("let bea", "beatles - let it be"),
+            Some(vec![0, 1, 2, 10, 11, 12])
+        );
+        assert_eq!(
+            positions("bea let", "beatles - let it be"),
+            Some(vec![0, 1, 2, 10, 11, 12])
+        );
+        assert_eq!(positions("let abba", "beatles - let it be"), None);
+        // Positions two terms share are listed once.
+        assert_eq!(positions("be be", "beatles"), Some(vec![0, 1]));
+        assert_eq!(points("ab ab", "ab"), 2 * points("ab", "ab"));
+    }
+
+    #[test]
+    fn case() {
+        assert_eq!(positions("BEA", "beatles"), Some(vec![0, 1, 2]));
+        assert_eq!(positions("bea", "BEATLES"), Some(vec![0, 1, 2]));
+        assert_eq!(points("Bea", "bEATLES"), points("bea", "beatles"));
+        assert_eq!(positions("ÉTÉ", "un été"), Some(vec![3, 4, 5]));
+    }
+
+    #[test]
+    fn order() {
+        assert_eq!(positions("ba", "ab"), None);
+        assert_eq!(positions("sg", "a song"), Some(vec![2, 5]));
+    }
+
+    #[test]
+    fn rating() {
+        // A match, a word boundary, then a match following the last.
+        assert_eq!(points("ab", "ab"), MATCH + BOUNDARY + MATCH + CONSECUTIVE);
+        assert!(points("ab", "x ab") > points("ab", "xab"));
+        assert!(points("ab", "xab") > points("ab", "xaxb"));
+        assert!(points("ab", "xaxb") > points("ab", "xaxxxb"));
+        // Gaps stop costing more past a point.
+        assert_eq!(
+            points("ab", &format!("a{}b", "x".repeat(16))),
+            points("ab", &format!("a{}b", "x".repeat(40)))
+        );
+    }
+
+    #[test]
+    fn best_alignment() {
+        // The scattered match from the first `a` loses to the word at the end.
+        assert_eq!(positions("ab", "a-xb ab"), Some(vec![5, 6]));
+        assert_eq!(positions("song", "sxoxnxg song"), Some(vec![8, 9, 10, 11]));
+    }
+}
@@ -0,0 +1,348 @@
+//! Cover art drawn in pixels, through the kitty graphics protocol or as
+//! sixels, in terminals that have either. Others get half-block cells.
+
+use crate::image::Image;
+
+use serde::Deserialize;
+use std::env;
+use std::io::{self, Write};
+use std::str;
+use termion::cursor;
+use tui::layout::Rect;
+
+/// How cover art is drawn, from the `graphics` setting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Protocol {
+    /// Whatever the terminal is found to support.
+    #[default]
+    Auto,
+    Kitty,
+    Sixel,
+    /// Half-block cells, which any terminal with colours draws.
+    Blocks,
+}
+
+/// Id of the one picture flux shows in kitty, so it can be taken down again.
+const ID: u32 = 1;
+
+/// Cell size assumed when the terminal does not say how many pixels it has.
+const CELL: (usize, usize) = (10, 20);
+
+/// Sixel colour registers used, a 6×7×6 cube with more steps of green, which
+/// the eye tells apart best.
+const PALETTE: usize = 252;
+
+/// The picture on screen: where, of which album, and how large the terminal
+/// was when it went up.
+#[derive(PartialEq)]
+struct Shown {
+    area: Rect,
+    key: String,
+    screen: (u16, u16),
+}
+
+pub struct Graphics {
+    /// `Auto` until the terminal answers the device attributes query.
+    protocol: Protocol,
+    shown: Option<Shown>,
+}
+
+impl Graphics {
+    /// Settles on a protocol for `setting`. With `auto`, kitty and terminals
+    /// that speak its protocol are known by their environment. Others are
+    /// asked for their device attributes, and the answer goes to `answered`.
+    pub fn new(setting: Protocol, out: &mut impl Write) -> io::Result<Graphics> {
+        let protocol = match setting {
+            Protocol::Auto => detect(),
+            protocol => protocol,
+        };
+        if protocol == Protocol::Auto {
+            write!(out, "\x1b[c")?;
+            out.flush()?;
+        }
+        Ok(Graphics {
+            protocol,
+            shown: None,
+        })
+    }
+
+    /// Whether cover art is drawn in pixels, which leaves its cells blank.
+    pub fn pixels(&self) -> bool {
+        matches!(self.protocol, Protocol::Kitty | Protocol::Sixel)
+    }
+
+    /// Takes the terminal's device attributes, where 4 stands for sixels.
+    pub fn answered(&mut self, attributes: &[u16]) {
+        if self.protocol == Protocol::Auto {
+            self.protocol = if attributes.contains(&4) {
+                Protocol::Sixel
+            } else {
+                Protocol::Blocks
+            };
+        }
+    }
+
+    /// Puts `image`, the cover of the album `key`, over `area`, or takes the
+    /// picture down for `None`. Nothing is sent while the same picture stays
+    /// where it is.
+    ///
+    /// Sixels are part of the text they were drawn over, so a sixel cannot be
+    /// taken down. `true` asks for the screen to be cleared and drawn again,
+    /// after which `show` is called once more.
+    pub fn show(
+        &mut self,
+        out: &mut impl Write,
+        cover: Option<(Rect, String, &Image)>,
+    ) -> io::Result<bool> {
+        if !self.pixels() {
+            return Ok(false);
+        }
+        let screen = termion::terminal_size()?;
+        let next = cover.as_ref().map(|(area, key, _)| Shown {
+            area: *area,
+            key: key.clone(),
+            screen,
+        });
+        if next == self.shown {
+            return Ok(false);
+        }
+        let previous = self.shown.take();
+        if self.protocol == Protocol::Kitty && previous.is_some() {
+            write!(out, "\x1b_Ga=d,d=I,i={},q=2\x1b\\", ID)?;
+        }
+        // A new screen size clears the screen anyway.
+        if self.protocol == Protocol::Sixel && previous.is_some_and(|p| p.screen == screen) {
+            out.flush()?;
+            return Ok(true);
+        }
+        if let Some((area, _, image)) = cover {
+            let image = fit(image, area, cell(screen));
+            write!(out, "\x1b7{}", cursor::Goto(area.x + 1, area.y + 1))?;
+            match self.protocol {
+                Protocol::Kitty => kitty(out, &image)?,
+                _ => out.write_all(&sixel(&image))?,
+            }
+            write!(out, "\x1b8")?;
+        }
+        out.flush()?;
+        self.shown = next;
+        Ok(false)
+    }
+}
+
+/// The protocol the environment gives away, or `Auto` to ask the terminal.
+/// Inside tmux or screen the variables belong to the terminal outside, which
+/// the multiplexer does not pass pictures through to.
+fn detect() -> Protocol {
+    if env::var_os("TMUX").is_some() || env::var_os("STY").is_some() {
+        return Protocol::Auto;
+    }
+    let term = env::var("TERM").unwrap_or_default();
+    let program = env::var("TERM_PROGRAM").unwrap_or_default();
+    if env::var_os("KITTY_WINDOW_ID").is_some()
+        || term == "xterm-kitty"
+        || term == "xterm-ghostty"
+        || program == "WezTerm"
+        || program == "ghostty"
+    {
+        Protocol::Kitty
+    } else {
+        Protocol::Auto
+    }
+}
+
+/// The attributes in an answer to the device attributes query, such as
+/// `ESC [ ? 62 ; 4 c`, at the start of `bytes`, with the answer's length.
+pub fn attributes(bytes: &[u8]) -> Option<(usize, Vec<u16>)> {
+    let rest = bytes.strip_prefix(b"\x1b[?")?;
+    let end = rest
+        .iter()
+        .position(|&b| !b.is_ascii_digit() && b != b';')?;
+    if rest[end] != b'c' {
+        return None;
+    }
+    let attributes = str::from_utf8(&rest[..end])
+        .ok()?
+        .split(';')
+        .filter_map(|n| n.parse().ok())
+        .collect();
+    Some((3 + end + 1, attributes))
+}
+
+/// Pixels to a cell, worked out from the size of the terminal in both.
+fn cell((columns, rows): (u16, u16)) -> (usize, usize) {
+    match termion::terminal_size_pixels() {
+        Ok((width, height)) if columns > 0 && rows > 0 && width > 0 && height > 0 => (
+            (width / columns).max(1) as usize,
+            (height / rows).max(1) as usize,
+        ),
+        _ => CELL,
+    }
+}
+
+/// `image` as large as fits in `area`, keeping its shape.
+fn fit(image: &Image, area: Rect, (width, height): (usize, usize)) -> Image {
+    let (width, height) = (area.width as usize * width, area.height as usize * height);
+    let scale = (width as f64 / image.width as f64).min(height as f64 / image.height as f64);
+    let width = ((image.width as f64 * scale) as usize).clamp(1, width.max(1));
+    let height = ((image.height as f64 * scale) as usize).clamp(1, height.max(1));
+    image.resize(width, height)
+}
+
+/// Sends `image` with the kitty graphics protocol as raw RGB, in the chunks it
+/// takes at most, and shows it at the cursor without moving it.
+fn kitty(out: &mut impl Write, image: &Image) -> io::Result<()> {
+    let bytes: Vec<u8> = image.pixels.iter().flatten().copied().collect();
+    let data = base64(&bytes);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        if i == 0 {
+            write!(
+                out,
+                "\x1b_Ga=T,f=24,s={},v={},i={},C=1,q=2,m={};",
+                image.width, image.height, ID, more
+            )?;
+        } else {
+            write!(out, "\x1b_Gm={};", more)?;
+        }
+        out.write_all(chunk)?;
+        write!(out, "\x1b\\")?;
+    }
+    Ok(())
+}
+
+/// `image` as sixels. Each band of six rows has a line per colour in it, in
+/// which every character sets the rows that colour has in one column.
+fn sixel(image: &Image) -> Vec<u8> {
+    let mut out = format!("\x1bPq\"1;1;{};{}", image.width, image.height).into_bytes();
+    for register in 0..PALETTE {
+        let [r, g, b] = colour(register).map(|c| c as usize * 100 / 255);
+        out.extend_from_slice(format!("#{};2;{};{};{}", register, r, g, b).as_bytes());
+    }
+    for top in (0..image.height).step_by(6) {
+        let mut bands: Vec<Option<Vec<u8>>> = vec![None; PALETTE];
+        for dy in 0..(image.height - top).min(6) {
+            for x in 0..image.width {
+                let band = bands[register(image.pixel(x, top + dy))]
+                    .get_or_insert_with(|| vec![0; image.width]);
+                band[x] |= 1 << dy;
+            }
+        }
+        for (register, band) in bands.iter().enumerate() {
+            if let Some(band) = band {
+                out.extend_from_slice(format!("#{}", register).as_bytes());
+                runs(&mut out, band);
+                out.push(b'$');
+            }
+        }
+        out.push(b'-');
+    }
+    out.extend_from_slice(b"\x1b\\");
+    out
+}
+
+/// Writes `band` with repeats of more than three shortened to `!<count>`, and
+/// without the empty columns at the end.
+fn runs(out: &mut Vec<u8>, band: &[u8]) {
+    let end = band.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+    let mut x = 0;
+    while x < end {
+        let n = band[x..end].iter().take_while(|&&b| b == band[x]).count();
+        let c = band[x] + 63;
+        if n > 3 {
+            out.extend_from_slice(format!("!{}", n).as_bytes());
+            out.push(c);
+        } else {
+            out.resize(out.len() + n, c);
+        }
+        x += n;
+    }
+}
+
+/// The colour register nearest to `rgb`.
+fn register([r, g, b]: [u8; 3]) -> usize {
+    let level = |c: u8, steps: usize| (c as usize * (steps - 1) + 127) / 255;
+    level(r, 6) * 42 + level(g, 7) * 6 + level(b, 6)
+}
+
+fn colour(register: usize) -> [u8; 3] {
+    let value = |level: usize, steps: usize| (level * 255 / (steps - 1)) as u8;
+    [
+        value(register / 42, 6),
+        value(register / 6 % 7, 7),
+        value(register % 6, 6),
+    ]
+}
+
+pub fn base64(bytes: &[u8]) -> String {
+    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(CHARS[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_attributes() {
+        assert_eq!(attributes(b"\x1b[?62;4;22cj"), Some((11, vec![62, 4, 22])));
+        assert_eq!(attributes(b"\x1b[?c"), Some((4, vec![])));
+        assert_eq!(attributes(b"\x1b[?;;99999999c"), Some((14, vec![])));
+        // Cut short, or some other sequence.
+        assert_eq!(attributes(b"\x1b[?62;4"), None);
+        assert_eq!(attributes(b"\x1b[?"), None);
+        assert_eq!(attributes(b"\x1b["), None);
+        assert_eq!(attributes(b""), None);
+        assert_eq!(attributes(b"\x1b[?1;2R"), None);
+        assert_eq!(attributes(b"\x1b[A"), None);
+    }
+
+    #[test]
+    fn registers() {
+        for register in 0..PALETTE {
+            assert_eq!(super::register(colour(register)), register);
+        }
+        assert_eq!(super::register([250, 3, 3]), super::register([255, 0, 0]));
+    }
+
+    #[test]
+    fn sixel_bands() {
+        let red = [255, 0, 0];
+        let image = Image::new(5, 7, vec![red; 35]);
+        let out = sixel(&image);
+        let text = str::from_utf8(&out).unwrap();
+        let body = text.rsplit_once("#251;2;100;100;100").unwrap().1;
+        let r = super::register(red);
+        // All six rows, then the last row alone.
+        assert_eq!(body, format!("#{}!5~$-#{}!5@$-\x1b\\", r, r));
+    }
+
+    #[test]
+    fn kitty_chunks() {
+        let image = Image::new(40, 40, vec![[1, 2, 3]; 1600]);
+        let mut out = Vec::new();
+        kitty(&mut out, &image).unwrap();
+        let text = str::from_utf8(&out).unwrap();
+        let chunks: Vec<&str> = text.split("\x1b_G").skip(1).collect();
+        // 4800 bytes are 6400 in base64.
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].starts_with("a=T,f=24,s=40,v=40,i=1,C=1,q=2,m=1;"));
+        assert!(chunks[1].starts_with("m=0;"));
+    }
+}
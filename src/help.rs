@@ -0,0 +1,77 @@
+use crate::config::TABS;
+use crate::keys::{self, Keymap, CATEGORIES};
+use crate::library::{LibItem, LibKind};
+use crate::libs::{Data, Library};
+
+use termion::event::Key;
+use tui::style::{Modifier, Style};
+
+/// The popup listing the bindings of the tab it was opened in, under the
+/// headings of `keys::CATEGORIES`. It is built from the keymap, so it shows
+/// the bindings from the config as well as the defaults.
+pub struct Help {
+    pub rows: Library,
+    /// Width of the key column.
+    pub width: u16,
+    /// Name of the tab, or of the options pane.
+    pub scope: &'static str,
+}
+
+impl Help {
+    /// Lists the bindings of `scope`, a tab index or `config::OPTIONS`.
+    pub fn show(data: &mut Data, keymap: &Keymap, scope: usize) {
+        let bindings = keymap.bindings(scope);
+        let heading = Style::default()
+            .fg(data.colors.highlight)
+            .add_modifier(Modifier::BOLD);
+        let mut items = vec![];
+        for category in CATEGORIES {
+            let mut actions = bindings
+                .iter()
+                .filter(|(action, _)| action.category() == *category)
+                .peekable();
+            if actions.peek().is_none() {
+                continue;
+            }
+            if !items.is_empty() {
+                items.push(LibItem::new(String::from(" "), LibKind::None));
+            }
+            let mut item = LibItem::new(category.to_string(), LibKind::None);
+            item.style = heading;
+            items.push(item);
+            for (action, keys) in actions {
+                let keys: Vec<String> = keys.iter().map(ToString::to_string).collect();
+                let mut item = LibItem::new(action.describe(), LibKind::None);
+                item.cells = vec![format!("  {}", keys.join(", ")), action.describe()];
+                items.push(item);
+            }
+        }
+        let width = items
+            .iter()
+            .filter_map(|item| item.cells.first())
+            .map(|keys| keys.chars().count())
+            .max();
+        data.help = Some(Help {
+            rows: Library::newlib(items),
+            width: width.unwrap_or(0) as u16,
+            scope: TABS.get(scope).copied().unwrap_or("options"),
+        });
+    }
+}
+
+/// Feeds a key typed while the popup is open.
+pub fn input(key: Key, data: &mut Data) {
+    let wrap = data.wrap;
+    let help = match data.help.as_mut() {
+        Some(help) => help,
+        None => return,
+    };
+    match key {
+        Key::Esc | Key::Ctrl('c') | Key::Char('q') | Key::Char('?') => data.help = None,
+        _ => {
+            if let Some(motion) = keys::popup_motion(key) {
+                help.rows.motion(motion, wrap);
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::connection::{Connection, Offline};
+use crate::eve::Event;
+
+use mpd::idle::{Idle, Subsystem};
+use std::sync::mpsc::Sender;
+use std::thread;
+use termion::event::Key;
+
+/// Changes the main loop refreshes `Data` for.
+const WATCHED: &[Subsystem] = &[
+    Subsystem::Player,
+    Subsystem::Queue,
+    Subsystem::Database,
+    Subsystem::Mixer,
+    Subsystem::Options,
+    Subsystem::Playlist,
+];
+
+/// Keeps a second connection in `idle` and sends every change it reports as
+/// an event. A client that is idling cannot run other commands, hence the
+/// separate connection.
+///
+/// When the connection drops, `Event::Lost` is sent and the thread keeps
+/// trying to reconnect. Once it is back, every subsystem is reported as
+/// changed since nobody was watching in between.
+pub fn watch(connection: Connection, tx: Sender<Event<Key>>) {
+    thread::spawn(move || {
+        let mut offline: Option<Offline> = None;
+        loop {
+            if let Some(offline) = &offline {
+                thread::sleep(offline.retry_in());
+            }
+            let mut client = match connection.connect() {
+                Ok(client) => client,
+                Err(err) => {
+                    match offline.as_mut() {
+                        Some(offline) => offline.failed(&err),
+                        None => offline = Some(Offline::new(&connection.target, &err)),
+                    }
+                    continue;
+                }
+            };
+            let missed: &[Subsystem] = if offline.take().is_some() {
+                WATCHED
+            } else {
+                &[]
+            };
+            for &subsystem in missed {
+                if tx.send(Event::Changed(subsystem)).is_err() {
+                    return;
+                }
+            }
+            let err = loop {
+                match client.wait(WATCHED) {
+                    Ok(changed) => {
+                        for subsystem in changed {
+                            if tx.send(Event::Changed(subsystem)).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Err(err) => break err,
+                }
+            };
+            offline = Some(Offline::new(&connection.target, &err));
+            if tx.send(Event::Lost).is_err() {
+                return;
+            }
+        }
+    });
+}
@@ -0,0 +1,318 @@
+use zune_jpeg::zune_core::colorspace::ColorSpace;
+use zune_jpeg::zune_core::options::DecoderOptions;
+use zune_jpeg::JpegDecoder;
+
+/// The widest or tallest picture the decoders take. Buffers are sized from
+/// the header, so a bad one could otherwise ask for gigabytes.
+pub const MAX_SIDE: usize = 8192;
+
+/// The largest picture file read, from mpd or from disk. Covers are far
+/// smaller.
+pub const MAX_BYTES: usize = 16 << 20;
+
+/// An RGB picture, such as decoded cover art.
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// Rows top to bottom, left to right within a row.
+    pub pixels: Vec<[u8; 3]>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, pixels: Vec<[u8; 3]>) -> Image {
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Shrinks the picture to fit in `max` by `max` pixels, keeping its
+    /// shape. Every new pixel is the average of the ones it covers.
+    pub fn fit(&self, max: usize) -> Image {
+        if self.width <= max && self.height <= max {
+            return self.clone();
+        }
+        let scale = self.width.max(self.height) as f64 / max as f64;
+        let width = ((self.width as f64 / scale) as usize).max(1);
+        let height = ((self.height as f64 / scale) as usize).max(1);
+        self.resize(width, height)
+    }
+
+    /// Resamples to `width` by `height`, averaging when shrinking.
+    pub fn resize(&self, width: usize, height: usize) -> Image {
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let (top, bottom) = span(y, height, self.height);
+            for x in 0..width {
+                let (left, right) = span(x, width, self.width);
+                let mut sum = [0u32; 3];
+                for sy in top..bottom {
+                    for sx in left..right {
+                        let p = self.pixel(sx, sy);
+                        for c in 0..3 {
+                            sum[c] += p[c] as u32;
+                        }
+                    }
+                }
+                let n = ((bottom - top) * (right - left)) as u32;
+                pixels.push([(sum[0] / n) as u8, (sum[1] / n) as u8, (sum[2] / n) as u8]);
+            }
+        }
+        Image::new(width, height, pixels)
+    }
+}
+
+/// The source pixels under pixel `i` of `to`, when `from` pixels are resampled
+/// to `to`. Always at least one.
+fn span(i: usize, to: usize, from: usize) -> (usize, usize) {
+    let start = i * from / to;
+    let end = ((i + 1) * from / to).max(start + 1).min(from);
+    (start, end)
+}
+
+/// Decodes a JPEG or PNG file, going by its first bytes.
+pub fn decode(bytes: &[u8]) -> Option<Image> {
+    if bytes.starts_with(&[0xff, 0xd8]) {
+        decode_jpeg(bytes)
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        decode_png(bytes)
+    } else {
+        None
+    }
+}
+
+fn decode_jpeg(bytes: &[u8]) -> Option<Image> {
+    let options = DecoderOptions::default()
+        .jpeg_set_out_colorspace(ColorSpace::RGB)
+        .set_max_width(MAX_SIDE)
+        .set_max_height(MAX_SIDE);
+    let mut decoder = JpegDecoder::new_with_options(bytes, options);
+    let samples = decoder.decode().ok()?;
+    let (width, height) = decoder.dimensions()?;
+    from_samples(width, height, &samples)
+}
+
+fn decode_png(bytes: &[u8]) -> Option<Image> {
+    let mut decoder = png::Decoder::new_with_limits(
+        bytes,
+        png::Limits {
+            bytes: MAX_SIDE * MAX_SIDE * 4,
+        },
+    );
+    // Palettes and low bit depths come out as 8 bit samples.
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().ok()?;
+    let (width, height) = reader.info().size();
+    if width as usize > MAX_SIDE || height as usize > MAX_SIDE {
+        return None;
+    }
+    let mut samples = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut samples).ok()?;
+    from_samples(
+        frame.width as usize,
+        frame.height as usize,
+        &samples[..frame.buffer_size()],
+    )
+}
+
+/// A picture of interleaved 8 bit samples: grey, grey and alpha, RGB, or
+/// RGBA. Alpha is dropped.
+fn from_samples(width: usize, height: usize, samples: &[u8]) -> Option<Image> {
+    let count = width.checked_mul(height).filter(|&n| n > 0)?;
+    let channels = samples.len() / count;
+    let pixels = match channels {
+        1 | 2 => samples.chunks_exact(channels).map(|p| [p[0]; 3]).collect(),
+        3 | 4 => samples
+            .chunks_exact(channels)
+            .map(|p| [p[0], p[1], p[2]])
+            .collect(),
+        _ => return None,
+    };
+    Some(Image::new(width, height, pixels))
+}
+
+/// Writes `image` as an RGB PNG, for handing to other programs.
+pub fn encode_png(image: &Image) -> Vec<u8> {
+    let mut out = vec![];
+    let mut encoder = png::Encoder::new(&mut out, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let samples: Vec<u8> = image.pixels.iter().flatten().copied().collect();
+    // Writing to memory fails only for a picture that is not the size it says.
+    if let Ok(mut writer) = encoder.write_header() {
+        let _ = writer.write_image_data(&samples);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PNG with the chunks given, checksummed, after the signature.
+    fn chunks(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+        for (kind, body) in chunks {
+            out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            out.extend_from_slice(*kind);
+            out.extend_from_slice(body);
+            let crc = png_crc(*kind, body);
+            out.extend_from_slice(&crc.to_be_bytes());
+        }
+        out
+    }
+
+    fn png_crc(kind: &[u8], body: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in kind.iter().chain(body) {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xedb8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+
+    fn header(width: u32, height: u32) -> Vec<u8> {
+        let mut header = vec![];
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        header
+    }
+
+    /// A baseline JPEG of 8×8 flat grey: one component, tables with a single
+    /// code each, and one block holding nothing but a DC of 0.
+    const JPEG: &[u8] = &[
+        // SOI, and DQT with every step 1.
+        0xff, 0xd8, 0xff, 0xdb, 0x00, 0x43, 0x00, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+        // SOF0: 8 bits, 8×8, one component.
+        0xff, 0xc0, 0x00, 0x0b, 8, 0x00, 0x08, 0x00, 0x08, 1, 1, 0x11, 0,
+        // DHT for DC: one code of one bit, for 0.
+        0xff, 0xc4, 0x00, 0x14, 0x00, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        // The same for AC.
+        0xff, 0xc4, 0x00, 0x14, 0x10, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        // SOS, then a DC of 0 and end of block padded with ones, and EOI.
+        0xff, 0xda, 0x00, 0x08, 1, 1, 0x00, 0, 63, 0, 0x3f, 0xff, 0xd9,
+    ];
+
+    fn sample() -> Image {
+        let pixels = (0..48u8).map(|i| [i, i * 2, 255 - i]).collect();
+        Image::new(8, 6, pixels)
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let image = sample();
+        let decoded = decode(&encode_png(&image)).unwrap();
+        assert_eq!((decoded.width, decoded.height), (8, 6));
+        assert_eq!(decoded.pixels, image.pixels);
+    }
+
+    #[test]
+    fn png_truncated() {
+        let bytes = encode_png(&sample());
+        // Without the end chunk the picture is still all there.
+        for len in 0..bytes.len() {
+            if let Some(image) = decode(&bytes[..len]) {
+                assert!(len >= bytes.len() - 12, "{} bytes", len);
+                assert_eq!(image.pixels, sample().pixels);
+            }
+        }
+    }
+
+    #[test]
+    fn png_corrupted() {
+        let bytes = encode_png(&sample());
+        // Every byte past the signature flipped in turn, which the checksums
+        // and the decoder's own checks catch; none may panic.
+        for i in 8..bytes.len() {
+            let mut bad = bytes.clone();
+            bad[i] ^= 0xff;
+            let _ = decode(&bad);
+        }
+    }
+
+    #[test]
+    fn png_huge_header() {
+        let idat = [0x78, 0x01, 0x03, 0x00];
+        let bytes = chunks(&[
+            (b"IHDR", &header(65535, 65535)),
+            (b"IDAT", &idat),
+            (b"IEND", &[]),
+        ]);
+        assert!(decode(&bytes).is_none());
+    }
+
+    #[test]
+    fn png_short_data() {
+        // One empty stored block for a picture of 4×4.
+        let idat = [0x78, 0x01, 0x01, 0x00, 0x00, 0xff, 0xff];
+        let bytes = chunks(&[(b"IHDR", &header(4, 4)), (b"IDAT", &idat), (b"IEND", &[])]);
+        assert!(decode(&bytes).is_none());
+    }
+
+    #[test]
+    fn png_missing_chunks() {
+        let bytes = encode_png(&sample());
+        assert!(decode(&chunks(&[(b"IEND", &[])])).is_none());
+        // The header alone, with no data.
+        let header = &bytes[..8 + 12 + 13];
+        assert!(decode(&[header, &chunks(&[(b"IEND", &[])])[8..]].concat()).is_none());
+    }
+
+    #[test]
+    fn jpeg_huge_frame() {
+        // A baseline frame of 65535×65535 with three components, then EOI.
+        let mut data = vec![
+            0xff, 0xd8, 0xff, 0xc0, 0x00, 0x11, 8, 0xff, 0xff, 0xff, 0xff, 3,
+        ];
+        for id in 1..=3 {
+            data.extend_from_slice(&[id, 0x11, 0]);
+        }
+        data.extend_from_slice(&[0xff, 0xd9]);
+        assert!(decode(&data).is_none());
+    }
+
+    #[test]
+    fn jpeg_truncated_and_corrupted() {
+        let whole = decode(JPEG).unwrap();
+        // Scan data cut short reads as zeros, which for this picture is all
+        // of it, but headers cut short are no picture.
+        for len in 0..JPEG.len() {
+            if let Some(image) = decode(&JPEG[..len]) {
+                assert!(len >= JPEG.len() - 3, "{} bytes", len);
+                assert_eq!(image.pixels, whole.pixels);
+            }
+        }
+        for i in 2..JPEG.len() {
+            let mut bad = JPEG.to_vec();
+            bad[i] ^= 0xff;
+            let _ = decode(&bad);
+        }
+    }
+
+    #[test]
+    fn jpeg_decodes() {
+        let image = decode(JPEG).unwrap();
+        assert_eq!((image.width, image.height), (8, 8));
+        // A flat mid grey, give or take rounding.
+        for pixel in &image.pixels {
+            for &c in pixel {
+                assert!((120..=136).contains(&c), "{:?}", pixel);
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use crate::connection::Client;
+use crate::graphics::base64;
+use crate::keys;
+use crate::library::{LibItem, LibKind};
+use crate::libs::{Data, Library};
+use crate::menu;
+use crate::queue::Column;
+use crate::search::Search;
+
+use mpd::{error::Result, Song};
+use std::io::{self, Write};
+use termion::event::Key;
+
+/// One line of the info popup.
+struct Field {
+    label: String,
+    value: String,
+    /// Tag to search the database by when the line is picked.
+    tag: Option<String>,
+}
+
+/// The popup listing everything known about one song. Enter on a tag searches
+/// the database for other songs with the same value, `y` copies the value.
+pub struct Info {
+    pub rows: Library,
+    /// Width of the label column.
+    pub width: u16,
+    /// Whether the last key copied a value, to say so in the title.
+    pub copied: bool,
+    fields: Vec<Field>,
+}
+
+impl Info {
+    /// Shows `song`. The audio format is only known for the one playing.
+    pub fn show(data: &mut Data, song: Song) {
+        let field = |label: &str, value: String, tag: Option<&str>| Field {
+            label: label.to_string(),
+            value,
+            tag: tag.map(String::from),
+        };
+        let mut fields = vec![];
+        if let Some(title) = &song.title {
+            fields.push(field("Title", title.clone(), Some("Title")));
+        }
+        if let Some(name) = &song.name {
+            fields.push(field("Name", name.clone(), None));
+        }
+        for (tag, value) in &song.tags {
+            fields.push(field(tag, value.clone(), Some(tag)));
+        }
+        fields.push(field("File", song.file.clone(), None));
+        if song.duration.is_some() {
+            fields.push(field("Duration", Column::Duration.cell(&song), None));
+        }
+        if let Some(modified) = song.last_mod {
+            let modified = time::strftime("%Y-%m-%d %H:%M", &modified.to_local());
+            fields.push(field("Modified", modified.unwrap_or_default(), None));
+        }
+        let playing = data.current.place.is_some() && data.current.file == song.file;
+        if let (true, Some(audio)) = (playing, data.status.audio) {
+            let format = format!(
+                "{} Hz, {} bit, {} channels",
+                audio.rate, audio.bits, audio.chans
+            );
+            fields.push(field("Format", format, None));
+        }
+        if let (true, Some(bitrate)) = (playing, data.status.bitrate) {
+            fields.push(field("Bitrate", format!("{} kbps", bitrate), None));
+        }
+
+        let width = fields.iter().map(|f| f.label.chars().count()).max();
+        let items = fields
+            .iter()
+            .map(|f| {
+                let mut item = LibItem::new(f.value.clone(), LibKind::None);
+                item.cells = vec![format!(" {}", f.label), f.value.clone()];
+                item
+            })
+            .collect();
+        data.info = Some(Info {
+            rows: Library::newlib(items),
+            width: width.unwrap_or(0) as u16 + 1,
+            copied: false,
+            fields,
+        });
+    }
+
+    fn selected(&self) -> Option<&Field> {
+        self.fields.get(self.rows.state.selected()?)
+    }
+}
+
+/// Shows the row under the cursor in the library, playlists or queue tab, or
+/// the song playing anywhere else.
+pub fn open(data: &mut Data, client: &mut Client) -> Result<()> {
+    let (mut songs, _) = menu::highlighted(data, client)?;
+    let song = match songs.len() {
+        1 => songs.remove(0),
+        _ if !data.current.file.is_empty() => data.current.clone(),
+        _ => return Ok(()),
+    };
+    Info::show(data, song);
+    Ok(())
+}
+
+/// Feeds a key typed while the popup is open.
+pub fn input(key: Key, data: &mut Data) {
+    let wrap = data.wrap;
+    let info = match data.info.as_mut() {
+        Some(info) => info,
+        None => return,
+    };
+    info.copied = false;
+    match key {
+        Key::Esc | Key::Ctrl('c') | Key::Char('q') | Key::Char('i') => data.info = None,
+        Key::Char('y') => {
+            if let Some(field) = info.selected() {
+                info.copied = copy(&field.value).is_ok();
+            }
+        }
+        Key::Char('\n') => {
+            let search = info
+                .selected()
+                .and_then(|f| Some((f.tag.clone()?, f.value.clone())));
+            if let Some((tag, value)) = search {
+                data.info = None;
+                Search::tag(data, tag, value);
+            }
+        }
+        _ => {
+            if let Some(motion) = keys::popup_motion(key) {
+                info.rows.motion(motion, wrap);
+            }
+        }
+    }
+}
+
+/// Puts `text` on the clipboard through the terminal (OSC 52), which also
+/// works over ssh.
+fn copy(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()
+}
@@ -0,0 +1,272 @@
+use crate::config;
+use crate::control::{FAILED, UNREACHABLE};
+use crate::eve::Event;
+use crate::keys::Action;
+use crate::libs::Data;
+use crate::search::Search;
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use termion::event::Key;
+
+/// What the main loop tells the sender of a message: nothing, or why it could
+/// not be done.
+pub type Reply = Sender<Result<(), String>>;
+
+/// A line sent to a running flux over its socket.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// `tab <name|number>` shows a tab.
+    Tab(usize),
+    /// `search [<text>]` opens the library search with the text typed in.
+    Search(String),
+    /// `notify <text>` shows the text in the status bar for a while.
+    Notify(String),
+    /// `action <name>` runs an action as if its key was pressed.
+    Action(Action),
+}
+
+impl FromStr for Message {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Message, String> {
+        let (command, rest) = match line.trim().split_once(' ') {
+            Some((command, rest)) => (command, rest.trim()),
+            None => (line.trim(), ""),
+        };
+        match command {
+            "tab" => config::tab_index(rest).map(Message::Tab),
+            "search" => Ok(Message::Search(rest.to_string())),
+            "notify" if !rest.is_empty() => Ok(Message::Notify(rest.to_string())),
+            "action" => rest.parse().map(Message::Action),
+            "" => Err(String::from("empty message")),
+            _ => Err(format!("unknown message `{}`", line.trim())),
+        }
+    }
+}
+
+/// `$XDG_RUNTIME_DIR/flux.sock`, where a running flux takes messages.
+pub fn path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty())?;
+    Some(PathBuf::from(dir).join("flux.sock"))
+}
+
+/// The socket being listened on, removed again when dropped.
+pub struct Listener {
+    path: PathBuf,
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Takes messages on the socket and sends each as `Event::Remote`, answering
+/// `OK` or `ERR <reason>` once the main loop has handled it. Returns `None`
+/// without a runtime directory, or when another flux is listening already.
+pub fn listen(tx: Sender<Event<Key>>) -> Option<Listener> {
+    let path = path()?;
+    if UnixStream::connect(&path).is_ok() {
+        return None;
+    }
+    // Left over from a flux that did not exit cleanly.
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).ok()?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let tx = tx.clone();
+            thread::spawn(move || serve(stream, tx));
+        }
+    });
+    Some(Listener { path })
+}
+
+fn serve(stream: UnixStream, tx: Sender<Event<Key>>) -> io::Result<()> {
+    let mut writer = &stream;
+    for line in BufReader::new(&stream).lines() {
+        let result = match line?.parse::<Message>() {
+            Ok(message) => {
+                let (reply, answer) = mpsc::channel();
+                if tx.send(Event::Remote(message, reply)).is_err() {
+                    return Ok(());
+                }
+                answer
+                    .recv()
+                    .unwrap_or_else(|_| Err(String::from("flux is exiting")))
+            }
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(()) => writeln!(writer, "OK")?,
+            Err(err) => writeln!(writer, "ERR {}", err)?,
+        }
+    }
+    Ok(())
+}
+
+/// Carries out a message that does not need the server. Actions are run by
+/// the main loop, like keys.
+pub fn apply(message: Message, data: &mut Data) {
+    match message {
+        Message::Tab(tab) => {
+            data.options = false;
+            data.tabindex = tab;
+        }
+        Message::Search(text) => {
+            // Start over from the whole library rather than the last results.
+            if let Some(search) = data.search.take() {
+                data.library = search.source;
+            }
+            Search::open(data);
+            if let Some(search) = data.search.as_mut() {
+                search.query = text;
+                data.library = search.filter();
+            }
+        }
+        Message::Notify(text) => data.notify(text),
+        Message::Action(_) => {}
+    }
+}
+
+/// Sends `message` to the running flux and returns the exit status for `flux
+/// send`.
+pub fn send(message: &str) -> i32 {
+    let stream = match path().map(UnixStream::connect) {
+        Some(Ok(stream)) => stream,
+        _ => {
+            eprintln!("flux: no running flux to send to");
+            return UNREACHABLE;
+        }
+    };
+    match exchange(stream, message).as_deref().map(str::trim_end) {
+        Ok("OK") => 0,
+        Ok(answer) => {
+            eprintln!("flux: {}", answer.strip_prefix("ERR ").unwrap_or(answer));
+            FAILED
+        }
+        Err(err) => {
+            eprintln!("flux: {}", err);
+            FAILED
+        }
+    }
+}
+
+fn exchange(stream: UnixStream, message: &str) -> io::Result<String> {
+    writeln!(&stream, "{}", message)?;
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer)?;
+    Ok(answer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages() {
+        assert_eq!("tab queue".parse(), Ok(Message::Tab(3)));
+        assert_eq!("tab 1".parse(), Ok(Message::Tab(0)));
+        assert_eq!("  tab   library ".parse(), Ok(Message::Tab(1)));
+        assert_eq!("search".parse(), Ok(Message::Search(String::new())));
+        assert_eq!(
+            "search  some band ".parse(),
+            Ok(Message::Search("some band".into()))
+        );
+        assert_eq!(
+            "notify hello there".parse(),
+            Ok(Message::Notify("hello there".into()))
+        );
+        assert_eq!(
+            "action seek-forward".parse(),
+            Ok(Message::Action(Action::SeekForward))
+        );
+    }
+
+    #[test]
+    fn unknown_messages() {
+        assert_eq!(
+            "play".parse::<Message>(),
+            Err(String::from("unknown message `play`"))
+        );
+        assert_eq!(
+            "Tab queue".parse::<Message>(),
+            Err(String::from("unknown message `Tab queue`"))
+        );
+        assert_eq!("".parse::<Message>(), Err(String::from("empty message")));
+        assert_eq!("   ".parse::<Message>(), Err(String::from("empty message")));
+    }
+
+    #[test]
+    fn bad_arguments() {
+        let tabs = "expected a tab number from 1 to 5 or one of now-playing, library, playlists, \
+                    queue, settings";
+        assert_eq!("tab".parse::<Message>(), Err(String::from(tabs)));
+        assert_eq!("tab 0".parse::<Message>(), Err(String::from(tabs)));
+        assert_eq!("tab 6".parse::<Message>(), Err(String::from(tabs)));
+        assert_eq!("tab Queue".parse::<Message>(), Err(String::from(tabs)));
+        assert_eq!(
+            "notify".parse::<Message>(),
+            Err(String::from("unknown message `notify`"))
+        );
+        assert_eq!(
+            "action".parse::<Message>(),
+            Err(String::from("unknown action ``"))
+        );
+        assert_eq!(
+            "action dance".parse::<Message>(),
+            Err(String::from("unknown action `dance`"))
+        );
+    }
+
+    #[test]
+    fn replies() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || serve(server, tx));
+        // Stands in for the main loop, which turns notices down.
+        thread::spawn(move || {
+            for event in rx {
+                if let Event::Remote(message, reply) = event {
+                    let _ = reply.send(match message {
+                        Message::Notify(_) => Err(String::from("busy")),
+                        _ => Ok(()),
+                    });
+                }
+            }
+        });
+        let mut answers = BufReader::new(&client).lines();
+        let mut ask = |line: &str| {
+            writeln!(&client, "{}", line).unwrap();
+            answers.next().unwrap().unwrap()
+        };
+        assert_eq!(ask("tab 2"), "OK");
+        assert_eq!(ask("action quit"), "OK");
+        assert_eq!(ask("notify hi"), "ERR busy");
+        assert_eq!(
+            ask("tab 9"),
+            format!("ERR {}", "tab 9".parse::<Message>().unwrap_err())
+        );
+        assert_eq!(ask("dance"), "ERR unknown message `dance`");
+        // The connection stays open after an error.
+        assert_eq!(ask("search x"), "OK");
+    }
+
+    #[test]
+    fn replies_while_exiting() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || serve(server, tx));
+        // The main loop takes the message but goes away without answering.
+        thread::spawn(move || {
+            let _ = rx.recv();
+        });
+        assert_eq!(exchange(client, "tab 1").unwrap(), "ERR flux is exiting\n");
+    }
+}
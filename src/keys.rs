@@ -0,0 +1,907 @@
+use crate::config::{OPTIONS, TABS};
+use crate::library::Motion;
+use crate::seek;
+
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use termion::event::Key;
+
+/// Everything a key can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    ToggleOptions,
+    NowPlaying,
+    Library,
+    Playlists,
+    Queue,
+    Settings,
+    Stop,
+    VolumeUp,
+    VolumeDown,
+    TogglePause,
+    UpdateDatabase,
+    Delete,
+    Down,
+    Up,
+    /// The first row, or the row of the count typed before it.
+    Top,
+    /// The last row, or the row of the count typed before it.
+    Bottom,
+    PageDown,
+    PageUp,
+    HalfPageDown,
+    HalfPageUp,
+    /// The first, middle and last rows on screen.
+    ScreenTop,
+    ScreenMiddle,
+    ScreenBottom,
+    NextTab,
+    PrevTab,
+    AddToQueue,
+    NextSong,
+    PrevSong,
+    Enter,
+    Search,
+    NextMatch,
+    PrevMatch,
+    Finder,
+    Visual,
+    Mark,
+    ClearSelection,
+    MoveUp,
+    MoveDown,
+    ClearQueue,
+    Crop,
+    Shuffle,
+    LoadPlaylist,
+    SaveQueue,
+    Rename,
+    AddToPlaylist,
+    SeekForward,
+    SeekBackward,
+    SeekForwardLong,
+    SeekBackwardLong,
+    /// Seek to a percentage of the current song.
+    SeekTo(u8),
+    ToggleRepeat,
+    ToggleRandom,
+    /// Cycle single mode through off, on and oneshot.
+    ToggleSingle,
+    ToggleConsume,
+    ToggleCrossfade,
+    /// Open the context menu for the highlighted row.
+    Menu,
+    /// Show everything known about the highlighted or the playing song.
+    Info,
+    /// Step the value under the cursor in the options pane.
+    Increase,
+    Decrease,
+    /// List the bindings of the tab.
+    Help,
+}
+
+/// Action names as used in the config file.
+const ACTIONS: &[(&str, Action)] = &[
+    ("quit", Action::Quit),
+    ("toggle-options", Action::ToggleOptions),
+    ("now-playing", Action::NowPlaying),
+    ("library", Action::Library),
+    ("playlists", Action::Playlists),
+    ("queue", Action::Queue),
+    ("settings", Action::Settings),
+    ("stop", Action::Stop),
+    ("volume-up", Action::VolumeUp),
+    ("volume-down", Action::VolumeDown),
+    ("toggle-pause", Action::TogglePause),
+    ("update-database", Action::UpdateDatabase),
+    ("delete", Action::Delete),
+    ("down", Action::Down),
+    ("up", Action::Up),
+    ("top", Action::Top),
+    ("bottom", Action::Bottom),
+    ("page-down", Action::PageDown),
+    ("page-up", Action::PageUp),
+    ("half-page-down", Action::HalfPageDown),
+    ("half-page-up", Action::HalfPageUp),
+    ("screen-top", Action::ScreenTop),
+    ("screen-middle", Action::ScreenMiddle),
+    ("screen-bottom", Action::ScreenBottom),
+    ("next-tab", Action::NextTab),
+    ("prev-tab", Action::PrevTab),
+    ("add-to-queue", Action::AddToQueue),
+    ("next-song", Action::NextSong),
+    ("prev-song", Action::PrevSong),
+    ("enter", Action::Enter),
+    ("search", Action::Search),
+    ("next-match", Action::NextMatch),
+    ("prev-match", Action::PrevMatch),
+    ("finder", Action::Finder),
+    ("visual", Action::Visual),
+    ("mark", Action::Mark),
+    ("clear-selection", Action::ClearSelection),
+    ("move-up", Action::MoveUp),
+    ("move-down", Action::MoveDown),
+    ("clear-queue", Action::ClearQueue),
+    ("crop", Action::Crop),
+    ("shuffle", Action::Shuffle),
+    ("load-playlist", Action::LoadPlaylist),
+    ("save-queue", Action::SaveQueue),
+    ("rename", Action::Rename),
+    ("add-to-playlist", Action::AddToPlaylist),
+    ("seek-forward", Action::SeekForward),
+    ("seek-backward", Action::SeekBackward),
+    ("seek-forward-long", Action::SeekForwardLong),
+    ("seek-backward-long", Action::SeekBackwardLong),
+    ("seek-to-0", Action::SeekTo(0)),
+    ("seek-to-10", Action::SeekTo(10)),
+    ("seek-to-20", Action::SeekTo(20)),
+    ("seek-to-30", Action::SeekTo(30)),
+    ("seek-to-40", Action::SeekTo(40)),
+    ("seek-to-50", Action::SeekTo(50)),
+    ("seek-to-60", Action::SeekTo(60)),
+    ("seek-to-70", Action::SeekTo(70)),
+    ("seek-to-80", Action::SeekTo(80)),
+    ("seek-to-90", Action::SeekTo(90)),
+    ("toggle-repeat", Action::ToggleRepeat),
+    ("toggle-random", Action::ToggleRandom),
+    ("toggle-single", Action::ToggleSingle),
+    ("toggle-consume", Action::ToggleConsume),
+    ("toggle-crossfade", Action::ToggleCrossfade),
+    ("menu", Action::Menu),
+    ("info", Action::Info),
+    ("increase", Action::Increase),
+    ("decrease", Action::Decrease),
+    ("help", Action::Help),
+];
+
+/// Headings of the help popup, in the order shown.
+pub const CATEGORIES: &[&str] = &[
+    "General",
+    "Tabs",
+    "Moving around",
+    "Playback",
+    "Seeking",
+    "Modes",
+    "Search",
+    "Selection",
+    "Queue and playlists",
+    "Options",
+];
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        ACTIONS.iter().find(|(_, a)| *a == self).unwrap().0
+    }
+
+    /// The heading the action is listed under in the help popup, one of
+    /// `CATEGORIES`.
+    pub fn category(self) -> &'static str {
+        match self {
+            Action::Quit
+            | Action::ToggleOptions
+            | Action::UpdateDatabase
+            | Action::Finder
+            | Action::Menu
+            | Action::Info
+            | Action::Help => "General",
+            Action::NowPlaying
+            | Action::Library
+            | Action::Playlists
+            | Action::Queue
+            | Action::Settings
+            | Action::NextTab
+            | Action::PrevTab => "Tabs",
+            Action::Down
+            | Action::Up
+            | Action::Top
+            | Action::Bottom
+            | Action::PageDown
+            | Action::PageUp
+            | Action::HalfPageDown
+            | Action::HalfPageUp
+            | Action::ScreenTop
+            | Action::ScreenMiddle
+            | Action::ScreenBottom
+            | Action::Enter => "Moving around",
+            Action::Stop
+            | Action::TogglePause
+            | Action::NextSong
+            | Action::PrevSong
+            | Action::VolumeUp
+            | Action::VolumeDown => "Playback",
+            Action::SeekForward
+            | Action::SeekBackward
+            | Action::SeekForwardLong
+            | Action::SeekBackwardLong
+            | Action::SeekTo(_) => "Seeking",
+            Action::ToggleRepeat
+            | Action::ToggleRandom
+            | Action::ToggleSingle
+            | Action::ToggleConsume
+            | Action::ToggleCrossfade => "Modes",
+            Action::Search | Action::NextMatch | Action::PrevMatch => "Search",
+            Action::Visual | Action::Mark | Action::ClearSelection => "Selection",
+            Action::AddToQueue
+            | Action::Delete
+            | Action::MoveUp
+            | Action::MoveDown
+            | Action::ClearQueue
+            | Action::Crop
+            | Action::Shuffle
+            | Action::LoadPlaylist
+            | Action::SaveQueue
+            | Action::Rename
+            | Action::AddToPlaylist => "Queue and playlists",
+            Action::Increase | Action::Decrease => "Options",
+        }
+    }
+
+    /// What the action does, as the help popup says it.
+    pub fn describe(self) -> String {
+        let text = match self {
+            Action::Quit => "Quit",
+            Action::ToggleOptions => "Open or close the options pane",
+            Action::NowPlaying => "Go to Now Playing",
+            Action::Library => "Go to the library",
+            Action::Playlists => "Go to the playlists",
+            Action::Queue => "Go to the queue",
+            Action::Settings => "Go to the settings",
+            Action::Stop => "Stop",
+            Action::VolumeUp => "Turn the volume up",
+            Action::VolumeDown => "Turn the volume down",
+            Action::TogglePause => "Play or pause",
+            Action::UpdateDatabase => "Rescan the music database",
+            Action::Delete => "Delete the highlighted or selected entries",
+            Action::Down => "Move down",
+            Action::Up => "Move up",
+            Action::Top => "Go to the first row, or to row N",
+            Action::Bottom => "Go to the last row, or to row N",
+            Action::PageDown => "Page down",
+            Action::PageUp => "Page up",
+            Action::HalfPageDown => "Half a page down",
+            Action::HalfPageUp => "Half a page up",
+            Action::ScreenTop => "Go to the top of the screen",
+            Action::ScreenMiddle => "Go to the middle of the screen",
+            Action::ScreenBottom => "Go to the bottom of the screen",
+            Action::NextTab => "Next tab",
+            Action::PrevTab => "Previous tab",
+            Action::AddToQueue => "Add to the queue",
+            Action::NextSong => "Next song",
+            Action::PrevSong => "Previous song",
+            Action::Enter => "Open or play the highlighted entry",
+            Action::Search => "Search the library",
+            Action::NextMatch => "Next match",
+            Action::PrevMatch => "Previous match",
+            Action::Finder => "Find a song anywhere in the database",
+            Action::Visual => "Start or end selecting a range",
+            Action::Mark => "Mark or unmark the song and move on",
+            Action::ClearSelection => "Clear the selection",
+            Action::MoveUp => "Move up a row",
+            Action::MoveDown => "Move down a row",
+            Action::ClearQueue => "Clear the queue",
+            Action::Crop => "Remove everything but the song playing",
+            Action::Shuffle => "Shuffle the selection or the whole queue",
+            Action::LoadPlaylist => "Replace the queue with the playlist",
+            Action::SaveQueue => "Save the queue as a playlist",
+            Action::Rename => "Rename the playlist",
+            Action::AddToPlaylist => "Add to a playlist",
+            Action::SeekForward => return format!("Seek {}s forward", seek::SHORT),
+            Action::SeekBackward => return format!("Seek {}s back", seek::SHORT),
+            Action::SeekForwardLong => return format!("Seek {}s forward", seek::LONG),
+            Action::SeekBackwardLong => return format!("Seek {}s back", seek::LONG),
+            Action::SeekTo(percent) => return format!("Seek to {}%", percent),
+            Action::ToggleRepeat => "Turn repeat on or off",
+            Action::ToggleRandom => "Turn random on or off",
+            Action::ToggleSingle => "Cycle single mode: off, on, oneshot",
+            Action::ToggleConsume => "Turn consume on or off",
+            Action::ToggleCrossfade => "Turn crossfade on or off",
+            Action::Menu => "Open the menu for the highlighted entry",
+            Action::Info => "Show everything about the song",
+            Action::Increase => "Increase the value",
+            Action::Decrease => "Decrease the value",
+            Action::Help => "Show this help",
+        };
+        text.to_string()
+    }
+
+    /// The move of the cursor the action makes, for actions that move it,
+    /// with the count typed before it as in `5j`.
+    pub fn motion(self, count: Option<usize>) -> Option<Motion> {
+        let n = count.unwrap_or(1);
+        let motion = match self {
+            Action::Down => Motion::Down(n),
+            Action::Up => Motion::Up(n),
+            Action::Top => Motion::Row(n - 1),
+            Action::Bottom => count.map_or(Motion::Last, |n| Motion::Row(n - 1)),
+            Action::PageDown => Motion::PageDown(n),
+            Action::PageUp => Motion::PageUp(n),
+            Action::HalfPageDown => Motion::HalfPageDown(n),
+            Action::HalfPageUp => Motion::HalfPageUp(n),
+            Action::ScreenTop => Motion::ScreenTop(n - 1),
+            Action::ScreenMiddle => Motion::ScreenMiddle,
+            Action::ScreenBottom => Motion::ScreenBottom(n - 1),
+            _ => return None,
+        };
+        Some(motion)
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Action, String> {
+        ACTIONS
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, action)| *action)
+            .ok_or_else(|| format!("unknown action `{}`", s))
+    }
+}
+
+impl<'de> Deserialize<'de> for Action {
+    fn deserialize<D>(deserializer: D) -> Result<Action, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Bindings active in every tab unless a tab binds the same keys itself.
+const DEFAULT_BINDINGS: &[(&str, Action)] = &[
+    ("q", Action::Quit),
+    ("o", Action::ToggleOptions),
+    ("O", Action::ToggleOptions),
+    ("1", Action::NowPlaying),
+    ("2", Action::Library),
+    ("3", Action::Playlists),
+    ("4", Action::Queue),
+    ("5", Action::Settings),
+    ("s", Action::Stop),
+    ("+", Action::VolumeUp),
+    ("-", Action::VolumeDown),
+    ("p", Action::TogglePause),
+    ("u", Action::UpdateDatabase),
+    ("down", Action::Down),
+    ("up", Action::Up),
+    ("j", Action::Down),
+    ("k", Action::Up),
+    ("g g", Action::Top),
+    ("home", Action::Top),
+    ("G", Action::Bottom),
+    ("end", Action::Bottom),
+    ("pagedown", Action::PageDown),
+    ("pageup", Action::PageUp),
+    ("ctrl-d", Action::HalfPageDown),
+    ("ctrl-u", Action::HalfPageUp),
+    ("H", Action::ScreenTop),
+    ("M", Action::ScreenMiddle),
+    ("L", Action::ScreenBottom),
+    ("right", Action::NextTab),
+    ("left", Action::PrevTab),
+    (".", Action::NextSong),
+    (",", Action::PrevSong),
+    ("enter", Action::Enter),
+    ("ctrl-p", Action::Finder),
+    ("f", Action::SeekForward),
+    ("b", Action::SeekBackward),
+    ("F", Action::SeekForwardLong),
+    ("B", Action::SeekBackwardLong),
+    ("% 0", Action::SeekTo(0)),
+    ("% 1", Action::SeekTo(10)),
+    ("% 2", Action::SeekTo(20)),
+    ("% 3", Action::SeekTo(30)),
+    ("% 4", Action::SeekTo(40)),
+    ("% 5", Action::SeekTo(50)),
+    ("% 6", Action::SeekTo(60)),
+    ("% 7", Action::SeekTo(70)),
+    ("% 8", Action::SeekTo(80)),
+    ("% 9", Action::SeekTo(90)),
+    ("r", Action::ToggleRepeat),
+    ("z", Action::ToggleRandom),
+    ("y", Action::ToggleSingle),
+    ("R", Action::ToggleConsume),
+    ("x", Action::ToggleCrossfade),
+    ("i", Action::Info),
+    ("?", Action::Help),
+];
+
+/// Bindings that only make sense in one tab, by tab index, or in the options
+/// pane.
+const DEFAULT_TAB_BINDINGS: &[(usize, &str, Action)] = &[
+    (1, "a", Action::AddToQueue),
+    (1, "/", Action::Search),
+    (1, "n", Action::NextMatch),
+    (1, "N", Action::PrevMatch),
+    (1, "A", Action::AddToPlaylist),
+    (1, "m", Action::Menu),
+    (2, "a", Action::AddToQueue),
+    (2, "l", Action::LoadPlaylist),
+    (2, "w", Action::SaveQueue),
+    (2, "e", Action::Rename),
+    (2, "d", Action::Delete),
+    (2, "K", Action::MoveUp),
+    (2, "J", Action::MoveDown),
+    (2, "m", Action::Menu),
+    (3, "d", Action::Delete),
+    (3, "v", Action::Visual),
+    (3, "space", Action::Mark),
+    (3, "esc", Action::ClearSelection),
+    (3, "K", Action::MoveUp),
+    (3, "J", Action::MoveDown),
+    (3, "C", Action::ClearQueue),
+    (3, "X", Action::Crop),
+    (3, "S", Action::Shuffle),
+    (3, "w", Action::SaveQueue),
+    (3, "m", Action::Menu),
+    (OPTIONS, "l", Action::Increase),
+    (OPTIONS, "right", Action::Increase),
+    (OPTIONS, "h", Action::Decrease),
+    (OPTIONS, "left", Action::Decrease),
+];
+
+/// A key sequence as written in the config: one key such as `"p"`, `"enter"`
+/// or `"ctrl-p"`, or several separated by spaces such as `"g g"`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeySeq(pub Vec<Key>);
+
+impl FromStr for KeySeq {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<KeySeq, String> {
+        if s == " " {
+            return Ok(KeySeq(vec![Key::Char(' ')]));
+        }
+        let keys = s
+            .split_whitespace()
+            .map(parse_key)
+            .collect::<Result<Vec<_>, _>>()?;
+        if keys.is_empty() {
+            return Err(String::from("empty key"));
+        }
+        Ok(KeySeq(keys))
+    }
+}
+
+impl fmt::Display for KeySeq {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<String> = self.0.iter().map(|key| key_name(*key)).collect();
+        f.write_str(&names.join(" "))
+    }
+}
+
+impl<'de> Deserialize<'de> for KeySeq {
+    fn deserialize<D>(deserializer: D) -> Result<KeySeq, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// One or more key sequences for a single action, written as `"p"` or
+/// `["p", "space"]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyList(pub Vec<KeySeq>);
+
+impl<'de> Deserialize<'de> for KeyList {
+    fn deserialize<D>(deserializer: D) -> Result<KeyList, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match Entry::deserialize(deserializer)? {
+            Entry::Keys(list) => Ok(list),
+            Entry::Tab(_) => Err(de::Error::custom("expected a key or a list of keys")),
+        }
+    }
+}
+
+/// A value in the `[keys]` table: either the keys for one action or a
+/// sub-table of bindings for a single tab.
+enum Entry {
+    Keys(KeyList),
+    Tab(HashMap<Action, KeyList>),
+}
+
+impl<'de> Deserialize<'de> for Entry {
+    fn deserialize<D>(deserializer: D) -> Result<Entry, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = Entry;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a key, a list of keys or a table of bindings")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Entry, E> {
+                v.parse()
+                    .map(|seq| Entry::Keys(KeyList(vec![seq])))
+                    .map_err(E::custom)
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Entry, A::Error> {
+                let mut keys = vec![];
+                while let Some(key) = seq.next_element()? {
+                    keys.push(key);
+                }
+                Ok(Entry::Keys(KeyList(keys)))
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Entry, A::Error> {
+                HashMap::deserialize(de::value::MapAccessDeserializer::new(map)).map(Entry::Tab)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// The `[keys]` table of the config. Plain entries bind an action in every
+/// tab, while sub-tables named after a tab (`[keys.queue]`) only apply there.
+/// `[keys.options]` applies while the options pane is open.
+#[derive(Debug, Default)]
+pub struct KeysConfig {
+    pub global: HashMap<Action, KeyList>,
+    pub tabs: HashMap<usize, HashMap<Action, KeyList>>,
+}
+
+impl<'de> Deserialize<'de> for KeysConfig {
+    fn deserialize<D>(deserializer: D) -> Result<KeysConfig, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = KeysConfig;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a table of key bindings")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<KeysConfig, A::Error> {
+                let mut keys = KeysConfig::default();
+                while let Some(name) = map.next_key::<String>()? {
+                    match map.next_value()? {
+                        Entry::Keys(list) => {
+                            let action = name.parse().map_err(de::Error::custom)?;
+                            keys.global.insert(action, list);
+                        }
+                        Entry::Tab(bindings) => match scope(&name) {
+                            Some(tab) => {
+                                keys.tabs.insert(tab, bindings);
+                            }
+                            None => {
+                                let msg = format!("`{}` is not a tab or `options`", name);
+                                return Err(de::Error::custom(msg));
+                            }
+                        },
+                    }
+                }
+                Ok(keys)
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+/// Index of the tab named `name`, or of the options pane.
+fn scope(name: &str) -> Option<usize> {
+    match name {
+        "options" => Some(OPTIONS),
+        _ => TABS.iter().position(|t| *t == name),
+    }
+}
+
+pub enum Resolved {
+    /// An action, with the count typed before it.
+    Action(Action, Option<usize>),
+    /// The keys so far start a longer sequence.
+    Pending,
+    Unbound,
+}
+
+/// Resolves keys to actions, per tab, keeping track of partially typed
+/// sequences. A tab's own bindings shadow the global ones.
+pub struct Keymap {
+    global: HashMap<Vec<Key>, Action>,
+    tabs: Vec<HashMap<Vec<Key>, Action>>,
+    pending: Vec<Key>,
+    /// Digits typed before a move, as the `7` of `7j`. A count starts with a
+    /// digit that has no binding of its own, so the tab keys act at once;
+    /// after that every digit adds to it, as `0` does in vim.
+    count: Option<usize>,
+}
+
+impl Keymap {
+    /// Builds the keymap from the defaults and the config. An action listed in
+    /// the config loses its default keys in the same scope.
+    pub fn new(config: &KeysConfig) -> Keymap {
+        let mut global = HashMap::new();
+        for (key, action) in DEFAULT_BINDINGS {
+            if !config.global.contains_key(action) {
+                global.insert(key.parse::<KeySeq>().unwrap().0, *action);
+            }
+        }
+        bind(&mut global, &config.global);
+
+        let mut tabs = vec![HashMap::new(); OPTIONS + 1];
+        for (tab, key, action) in DEFAULT_TAB_BINDINGS {
+            let overridden = config.global.contains_key(action)
+                || config.tabs.get(tab).is_some_and(|t| t.contains_key(action));
+            if !overridden {
+                tabs[*tab].insert(key.parse::<KeySeq>().unwrap().0, *action);
+            }
+        }
+        for (tab, bindings) in &config.tabs {
+            bind(&mut tabs[*tab], bindings);
+        }
+
+        Keymap {
+            global,
+            tabs,
+            pending: vec![],
+            count: None,
+        }
+    }
+
+    pub fn resolve(&mut self, tab: usize, key: Key) -> Resolved {
+        if self.pending.is_empty() {
+            if let Key::Char(c @ '0'..='9') = key {
+                let digit = c as usize - '0' as usize;
+                match self.count {
+                    Some(count) => {
+                        self.count = Some(count.saturating_mul(10).saturating_add(digit));
+                        return Resolved::Pending;
+                    }
+                    None if digit > 0
+                        && self.lookup(tab, &[key]).is_none()
+                        && !self.is_prefix(tab, &[key]) =>
+                    {
+                        self.count = Some(digit);
+                        return Resolved::Pending;
+                    }
+                    None => {}
+                }
+            }
+        }
+        match self.sequence(tab, key) {
+            Resolved::Pending => Resolved::Pending,
+            Resolved::Action(action, _) if action.motion(None).is_some() => {
+                Resolved::Action(action, self.count.take())
+            }
+            resolved => {
+                self.count = None;
+                resolved
+            }
+        }
+    }
+
+    fn sequence(&mut self, tab: usize, key: Key) -> Resolved {
+        self.pending.push(key);
+        if let Some(action) = self.lookup(tab, &self.pending) {
+            self.pending.clear();
+            return Resolved::Action(action, None);
+        }
+        if self.is_prefix(tab, &self.pending) {
+            return Resolved::Pending;
+        }
+        let retry = self.pending.len() > 1;
+        self.pending.clear();
+        if retry && key != Key::Esc {
+            return self.sequence(tab, key);
+        }
+        Resolved::Unbound
+    }
+
+    /// The key sequences that run each action in `tab`, with the global ones
+    /// the tab shadows left out. Actions come in the order of `ACTIONS`.
+    pub fn bindings(&self, tab: usize) -> Vec<(Action, Vec<KeySeq>)> {
+        let own = self.tabs.get(tab);
+        let global = self
+            .global
+            .iter()
+            .filter(|(keys, _)| !own.is_some_and(|t| t.contains_key(*keys)));
+        let mut bindings: Vec<(Action, Vec<KeySeq>)> = vec![];
+        for (keys, action) in own.into_iter().flatten().chain(global) {
+            let seq = KeySeq(keys.clone());
+            match bindings.iter_mut().find(|(a, _)| a == action) {
+                Some((_, seqs)) => seqs.push(seq),
+                None => bindings.push((*action, vec![seq])),
+            }
+        }
+        for (_, seqs) in bindings.iter_mut() {
+            // Shorter sequences first, then by name.
+            seqs.sort_by_key(|seq| (seq.0.len(), seq.to_string()));
+        }
+        bindings.sort_by_key(|(action, _)| ACTIONS.iter().position(|(_, a)| a == action));
+        bindings
+    }
+
+    fn lookup(&self, tab: usize, keys: &[Key]) -> Option<Action> {
+        self.tabs
+            .get(tab)
+            .and_then(|t| t.get(keys))
+            .or_else(|| self.global.get(keys))
+            .copied()
+    }
+
+    fn is_prefix(&self, tab: usize, keys: &[Key]) -> bool {
+        let scopes = self.tabs.get(tab).into_iter().chain(Some(&self.global));
+        scopes
+            .flat_map(|scope| scope.keys())
+            .any(|seq| seq.len() > keys.len() && seq.starts_with(keys))
+    }
+}
+
+/// The move a key makes in a popup list. Popups take keys as they come,
+/// without the keymap or counts.
+pub fn popup_motion(key: Key) -> Option<Motion> {
+    let motion = match key {
+        Key::Down | Key::Char('j') | Key::Ctrl('n') => Motion::Down(1),
+        Key::Up | Key::Char('k') | Key::Ctrl('p') => Motion::Up(1),
+        Key::Home | Key::Char('g') => Motion::Row(0),
+        Key::End | Key::Char('G') => Motion::Last,
+        Key::PageDown => Motion::PageDown(1),
+        Key::PageUp => Motion::PageUp(1),
+        Key::Ctrl('d') => Motion::HalfPageDown(1),
+        Key::Ctrl('u') => Motion::HalfPageUp(1),
+        Key::Char('H') => Motion::ScreenTop(0),
+        Key::Char('M') => Motion::ScreenMiddle,
+        Key::Char('L') => Motion::ScreenBottom(0),
+        _ => return None,
+    };
+    Some(motion)
+}
+
+fn bind(map: &mut HashMap<Vec<Key>, Action>, bindings: &HashMap<Action, KeyList>) {
+    for (action, keys) in bindings {
+        for seq in &keys.0 {
+            map.insert(seq.0.clone(), *action);
+        }
+    }
+}
+
+/// The name of `key` as `parse_key` reads it.
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Char('\n') => String::from("enter"),
+        Key::Char(' ') => String::from("space"),
+        Key::Char('\t') => String::from("tab"),
+        Key::Char(c) => c.to_string(),
+        Key::BackTab => String::from("backtab"),
+        Key::Backspace => String::from("backspace"),
+        Key::Esc => String::from("esc"),
+        Key::Up => String::from("up"),
+        Key::Down => String::from("down"),
+        Key::Left => String::from("left"),
+        Key::Right => String::from("right"),
+        Key::Home => String::from("home"),
+        Key::End => String::from("end"),
+        Key::PageUp => String::from("pageup"),
+        Key::PageDown => String::from("pagedown"),
+        Key::Delete => String::from("delete"),
+        Key::Insert => String::from("insert"),
+        Key::Ctrl(c) => format!("ctrl-{}", c),
+        Key::Alt(c) => format!("alt-{}", c),
+        Key::F(n) => format!("f{}", n),
+        _ => String::from("?"),
+    }
+}
+
+pub fn parse_key(s: &str) -> Result<Key, String> {
+    let lower = s.to_lowercase();
+    let single = |rest: &str| {
+        let mut chars = rest.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(format!("invalid key `{}`", s)),
+        }
+    };
+    let key = match lower.as_str() {
+        "enter" | "return" => Key::Char('\n'),
+        "space" => Key::Char(' '),
+        "tab" => Key::Char('\t'),
+        "backtab" => Key::BackTab,
+        "backspace" => Key::Backspace,
+        "esc" | "escape" => Key::Esc,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "delete" => Key::Delete,
+        "insert" => Key::Insert,
+        _ if lower.starts_with("ctrl-") && s.len() > 5 => Key::Ctrl(single(&lower[5..])?),
+        _ if lower.starts_with("alt-") && s.len() > 4 => Key::Alt(single(&s[4..])?),
+        _ if lower.len() > 1 && lower.starts_with('f') => match lower[1..].parse() {
+            Ok(n @ 1..=12) => Key::F(n),
+            _ => return Err(format!("invalid key `{}`", s)),
+        },
+        _ => Key::Char(single(s)?),
+    };
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(keymap: &mut Keymap, tab: usize, typed: &str) -> Vec<(Action, Option<usize>)> {
+        typed
+            .chars()
+            .filter_map(|c| match keymap.resolve(tab, Key::Char(c)) {
+                Resolved::Action(action, count) => Some((action, count)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counts() {
+        let mut keymap = Keymap::new(&KeysConfig::default());
+        assert_eq!(keys(&mut keymap, 1, "7j"), [(Action::Down, Some(7))]);
+        assert_eq!(keys(&mut keymap, 1, "62k"), [(Action::Up, Some(62))]);
+        assert_eq!(keys(&mut keymap, 1, "90G"), [(Action::Bottom, Some(90))]);
+        assert_eq!(keys(&mut keymap, 1, "8gg"), [(Action::Top, Some(8))]);
+        assert_eq!(keys(&mut keymap, 1, "j"), [(Action::Down, None)]);
+        // Digits in a sequence are not a count.
+        assert_eq!(keys(&mut keymap, 1, "%5"), [(Action::SeekTo(50), None)]);
+        // A count is dropped by anything other than a move.
+        assert_eq!(
+            keys(&mut keymap, 1, "7pj"),
+            [(Action::TogglePause, None), (Action::Down, None)]
+        );
+    }
+
+    #[test]
+    fn bound_digits() {
+        let mut keymap = Keymap::new(&KeysConfig::default());
+        // The tab keys act at once rather than wait to see if a move follows.
+        assert_eq!(keys(&mut keymap, 1, "2"), [(Action::Library, None)]);
+        assert_eq!(
+            keys(&mut keymap, 1, "4j"),
+            [(Action::Queue, None), (Action::Down, None)]
+        );
+        // Once unbound, they start counts as well.
+        let config: KeysConfig = toml::from_str("library = \"L\"").unwrap();
+        let mut keymap = Keymap::new(&config);
+        assert_eq!(keys(&mut keymap, 1, "2j"), [(Action::Down, Some(2))]);
+    }
+
+    #[test]
+    fn tab_bindings_keep_globals() {
+        // Only the options pane takes over global keys, to step values.
+        for (tab, keys, action) in DEFAULT_TAB_BINDINGS {
+            if *tab == OPTIONS {
+                continue;
+            }
+            let global = DEFAULT_BINDINGS.iter().find(|(k, _)| k == keys);
+            assert_eq!(
+                global, None,
+                "{:?} in tab {} hides a global key",
+                action, tab
+            );
+        }
+    }
+}
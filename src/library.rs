@@ -1,8 +1,11 @@
+use crate::queue::GAP;
+
+use mpd::Id;
 use tui::{
     buffer::Buffer,
     layout::Rect,
     style::Style,
-    text::{Spans, Text},
+    text::{Span, Spans, Text},
     widgets::{Block, StatefulWidget, Widget},
 };
 
@@ -10,6 +13,8 @@ use tui::{
 pub struct LibState {
     offset: usize,
     selected: Option<usize>,
+    /// Rows the list had room for when it was last drawn.
+    height: usize,
 }
 
 impl Default for LibState {
@@ -17,10 +22,30 @@ impl Default for LibState {
         LibState {
             offset: 0,
             selected: Some(0),
+            height: 0,
         }
     }
 }
 
+/// A move of the cursor in a list, with the count typed before it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Motion {
+    Down(usize),
+    Up(usize),
+    /// To a row by index, or the last one past the end.
+    Row(usize),
+    Last,
+    PageDown(usize),
+    PageUp(usize),
+    HalfPageDown(usize),
+    HalfPageUp(usize),
+    /// To a row counted down from the first one on screen.
+    ScreenTop(usize),
+    ScreenMiddle,
+    /// To a row counted up from the last one on screen.
+    ScreenBottom(usize),
+}
+
 impl LibState {
     pub fn selected(&self) -> Option<usize> {
         self.selected
@@ -36,6 +61,56 @@ impl LibState {
     pub fn offset(&mut self, index: usize) {
         self.offset = index;
     }
+
+    /// Index of the first row drawn.
+    pub fn top(&self) -> usize {
+        self.offset
+    }
+
+    /// Sets the rows on screen, for lists not drawn as a `Tree`.
+    pub fn height(&mut self, rows: usize) {
+        self.height = rows;
+    }
+
+    /// Moves the cursor over a list of `len` rows. Pages scroll the list along
+    /// with the cursor. Only moves by rows go around the ends, and only with
+    /// `wrap`; the rest stop at the first or last row.
+    pub fn motion(&mut self, motion: Motion, len: usize, wrap: bool) {
+        if len == 0 {
+            return;
+        }
+        let last = len - 1;
+        let selected = self.selected.unwrap_or(0).min(last);
+        let page = self.height.max(1);
+        let half = (page / 2).max(1);
+        let top = self.offset.min(last);
+        let bottom = (top + page - 1).min(last);
+        let mut scroll = |rows: usize, down: bool| {
+            if down {
+                self.offset = (top + rows).min(len.saturating_sub(page));
+                (selected + rows).min(last)
+            } else {
+                self.offset = top.saturating_sub(rows);
+                selected.saturating_sub(rows)
+            }
+        };
+        let row = match motion {
+            Motion::Down(n) if wrap => (selected + n % len) % len,
+            Motion::Down(n) => selected.saturating_add(n).min(last),
+            Motion::Up(n) if wrap => (selected + len - n % len) % len,
+            Motion::Up(n) => selected.saturating_sub(n),
+            Motion::Row(index) => index.min(last),
+            Motion::Last => last,
+            Motion::PageDown(n) => scroll(page.saturating_mul(n), true),
+            Motion::PageUp(n) => scroll(page.saturating_mul(n), false),
+            Motion::HalfPageDown(n) => scroll(half.saturating_mul(n), true),
+            Motion::HalfPageUp(n) => scroll(half.saturating_mul(n), false),
+            Motion::ScreenTop(n) => top.saturating_add(n).min(bottom),
+            Motion::ScreenMiddle => top + (bottom - top) / 2,
+            Motion::ScreenBottom(n) => bottom.saturating_sub(n).max(top),
+        };
+        self.selected = Some(row);
+    }
 }
 
 #[derive(PartialEq)]
@@ -56,11 +131,39 @@ impl Clone for LibKind {
     }
 }
 
+/// The song behind a row, so actions don't have to go by its display text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SongRef {
+    None,
+    /// A song in the database, by file URI.
+    File(String),
+    /// An entry in the queue.
+    Queue {
+        id: Id,
+        pos: u32,
+    },
+}
+
+impl SongRef {
+    /// The queue id, for rows of the queue.
+    pub fn id(&self) -> Option<Id> {
+        match self {
+            SongRef::Queue { id, .. } => Some(*id),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct LibItem {
     pub content: String,
     pub style: Style,
     pub tag: LibKind,
+    pub song: SongRef,
+    /// Char positions in `content` to draw with the tree's match style.
+    pub matches: Vec<usize>,
+    /// Column texts, drawn instead of `content` when the tree has columns.
+    pub cells: Vec<String>,
 }
 
 impl LibItem {
@@ -69,8 +172,16 @@ impl LibItem {
             content,
             style: Style::default(),
             tag,
+            song: SongRef::None,
+            matches: vec![],
+            cells: vec![],
         }
     }
+
+    pub fn song(mut self, song: SongRef) -> LibItem {
+        self.song = song;
+        self
+    }
 }
 
 pub struct Tree<'a> {
@@ -79,6 +190,10 @@ pub struct Tree<'a> {
     style: Style,
     highlight_style: Style,
     highlight_symbol: Option<&'a str>,
+    match_style: Style,
+    widths: Vec<u16>,
+    marked: Vec<usize>,
+    marked_style: Style,
 }
 
 impl<'a> Tree<'a> {
@@ -92,6 +207,10 @@ impl<'a> Tree<'a> {
             items: &items.into(),
             highlight_style: Style::default(),
             highlight_symbol: None,
+            match_style: Style::default(),
+            widths: vec![],
+            marked: vec![],
+            marked_style: Style::default(),
         }
     }
 
@@ -115,6 +234,57 @@ impl<'a> Tree<'a> {
         self
     }
 
+    pub fn match_style(mut self, style: Style) -> Tree<'a> {
+        self.match_style = style;
+        self
+    }
+
+    /// Rows to draw with `marked_style`, on top of their own style.
+    pub fn marked(mut self, rows: Vec<usize>) -> Tree<'a> {
+        self.marked = rows;
+        self
+    }
+
+    pub fn marked_style(mut self, style: Style) -> Tree<'a> {
+        self.marked_style = style;
+        self
+    }
+
+    /// Lays items out in columns of the given widths, see `LibItem::cells`.
+    pub fn columns(mut self, widths: Vec<u16>) -> Tree<'a> {
+        self.widths = widths;
+        self
+    }
+
+    fn spans(&self, item: &'a LibItem) -> Spans<'a> {
+        if !self.widths.is_empty() && !item.cells.is_empty() {
+            let mut spans = vec![];
+            for (cell, &width) in item.cells.iter().zip(&self.widths) {
+                let text: String = cell.chars().take(width as usize).collect();
+                let pad = width as usize - text.chars().count() + GAP as usize;
+                spans.push(Span::raw(text));
+                spans.push(Span::raw(" ".repeat(pad)));
+            }
+            return Spans::from(spans);
+        }
+        if item.matches.is_empty() {
+            return Spans::from(item.content.clone());
+        }
+        let spans: Vec<Span> = item
+            .content
+            .chars()
+            .enumerate()
+            .map(|(i, c)| {
+                if item.matches.contains(&i) {
+                    Span::styled(c.to_string(), self.match_style)
+                } else {
+                    Span::raw(c.to_string())
+                }
+            })
+            .collect();
+        Spans::from(spans)
+    }
+
     fn get_items_bounds(
         &self,
         selected: Option<usize>,
@@ -181,6 +351,7 @@ impl<'a> StatefulWidget for Tree<'a> {
 
         let (start, end) = self.get_items_bounds(state.selected, state.offset, list_height);
         state.offset = start;
+        state.height = list_height;
 
         let highlight_symbol = self.highlight_symbol.unwrap_or("");
 
@@ -202,7 +373,10 @@ impl<'a> StatefulWidget for Tree<'a> {
                 height: Text::from(item.content.clone()).height() as u16,
             };
 
-            let item_style = self.style.patch(item.style);
+            let mut item_style = self.style.patch(item.style);
+            if self.marked.contains(&i) {
+                item_style = item_style.patch(self.marked_style);
+            }
             buf.set_style(area, item_style);
 
             let is_selected = state.selected.map(|s| s == i).unwrap_or(false);
@@ -221,12 +395,7 @@ impl<'a> StatefulWidget for Tree<'a> {
             } else {
                 (x, list_area.width)
             };
-            buf.set_spans(
-                elem_x,
-                y,
-                &Spans::from(item.content.clone()),
-                max_element_width as u16,
-            );
+            buf.set_spans(elem_x, y, &self.spans(item), max_element_width as u16);
             let rect = Rect::new(
                 symbol.chars().count() as u16,
                 y,
@@ -239,3 +408,73 @@ impl<'a> StatefulWidget for Tree<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cursor at `selected` in a list showing ten rows from `offset`.
+    fn state(selected: usize, offset: usize) -> LibState {
+        LibState {
+            offset,
+            selected: Some(selected),
+            height: 10,
+        }
+    }
+
+    fn moved(mut state: LibState, motion: Motion, wrap: bool) -> (usize, usize) {
+        state.motion(motion, 100, wrap);
+        (state.selected.unwrap(), state.offset)
+    }
+
+    #[test]
+    fn rows() {
+        assert_eq!(moved(state(5, 0), Motion::Down(1), false), (6, 0));
+        assert_eq!(moved(state(5, 0), Motion::Down(7), false), (12, 0));
+        assert_eq!(moved(state(99, 90), Motion::Down(1), false), (99, 90));
+        assert_eq!(moved(state(99, 90), Motion::Down(1), true), (0, 90));
+        assert_eq!(moved(state(2, 0), Motion::Up(5), false), (0, 0));
+        assert_eq!(moved(state(2, 0), Motion::Up(5), true), (97, 0));
+        assert_eq!(moved(state(2, 0), Motion::Row(41), false), (41, 0));
+        assert_eq!(moved(state(2, 0), Motion::Row(400), false), (99, 0));
+        assert_eq!(moved(state(2, 0), Motion::Last, true), (99, 0));
+    }
+
+    #[test]
+    fn pages() {
+        assert_eq!(moved(state(3, 0), Motion::PageDown(1), false), (13, 10));
+        assert_eq!(moved(state(3, 0), Motion::PageDown(2), false), (23, 20));
+        assert_eq!(moved(state(95, 88), Motion::PageDown(1), true), (99, 90));
+        assert_eq!(moved(state(13, 10), Motion::PageUp(1), false), (3, 0));
+        assert_eq!(moved(state(3, 2), Motion::PageUp(1), true), (0, 0));
+        assert_eq!(moved(state(3, 0), Motion::HalfPageDown(1), false), (8, 5));
+        assert_eq!(moved(state(8, 5), Motion::HalfPageUp(1), false), (3, 0));
+    }
+
+    #[test]
+    fn screen() {
+        assert_eq!(moved(state(25, 20), Motion::ScreenTop(0), false), (20, 20));
+        assert_eq!(moved(state(25, 20), Motion::ScreenTop(3), false), (23, 20));
+        assert_eq!(moved(state(25, 20), Motion::ScreenMiddle, false), (24, 20));
+        assert_eq!(
+            moved(state(25, 20), Motion::ScreenBottom(0), false),
+            (29, 20)
+        );
+        assert_eq!(
+            moved(state(25, 20), Motion::ScreenBottom(40), false),
+            (20, 20)
+        );
+        // The screen runs out before the list does.
+        assert_eq!(
+            moved(state(97, 95), Motion::ScreenBottom(0), false),
+            (99, 95)
+        );
+    }
+
+    #[test]
+    fn empty() {
+        let mut state = state(0, 0);
+        state.motion(Motion::Down(1), 0, true);
+        assert_eq!(state.selected, Some(0));
+    }
+}
@@ -1,7 +1,24 @@
-use crate::library::{LibItem, LibKind, LibState};
-use mpd::{song::QueuePlace, song::Song, status::Status, Query, Term};
+use crate::art;
+use crate::config;
+use crate::connection::{Client, Connection, Offline};
+use crate::finder::Finder;
+use crate::help::Help;
+use crate::info::Info;
+use crate::library::{LibItem, LibKind, LibState, Motion, SongRef};
+use crate::lyrics;
+use crate::menu::Menu;
+use crate::modes::{self, Modes};
+use crate::prompt::Prompt;
+use crate::queue::{self, Column, Selection};
+use crate::search::Search;
+use crate::ui::Areas;
+use crate::worker::{Check, Request};
+use mpd::{error::Result, song::Song, status::State, status::Status, Query, Term};
+use serde::Deserialize;
 use std::borrow::Cow::Borrowed;
 use std::iter::FromIterator;
+use std::mem;
+use std::time::{Duration, Instant};
 use tui::{style::Color, style::Style, widgets::ListState};
 
 pub struct Data {
@@ -14,63 +31,74 @@ pub struct Data {
     pub settings: Settings,
     pub status: Status,
     pub colors: ColorScheme,
+    pub columns: Vec<Column>,
     pub tabindex: usize,
+    /// Whether moving past either end of a list goes around to the other.
+    pub wrap: bool,
     pub current: mpd::song::Song,
     pub style: tui::style::Style,
     pub options: bool,
+    /// Cursor in the options pane.
+    pub options_state: ListState,
+    pub modes: Modes,
+    /// Cover art, kept over reconnects.
+    pub art: art::Cache,
+    pub lyrics: lyrics::Pane,
+    /// Where to reach the server for commands the client has no call for.
+    pub connection: Connection,
+    pub offline: Option<Offline>,
+    pub search: Option<Search>,
+    pub finder: Option<Finder>,
+    /// Context menu for the highlighted row.
+    pub menu: Option<Menu>,
+    pub info: Option<Info>,
+    pub help: Option<Help>,
+    pub selection: Selection,
+    /// Stored playlist open in the playlists tab.
+    pub playlist: Option<String>,
+    pub prompt: Option<Prompt>,
+    /// When `status.elapsed` was last brought up to date.
+    pub ticked: Instant,
+    /// Slow commands for the worker, sent on by the main loop.
+    pub requests: Vec<Request>,
+    /// Whether the worker has requests in flight.
+    pub busy: bool,
+    pub areas: Areas,
+    /// Where the progress bar is being dragged to, as a share of the song.
+    pub drag: Option<f64>,
+    /// Last click on a list row, to tell double clicks apart.
+    pub click: Option<(Instant, usize)>,
+    /// Shown in the status bar until `NOTICE` has passed.
+    pub notice: Option<(String, Instant)>,
 }
 
-impl<'a> Data {
-    pub fn new(client: &mut mpd::Client) -> Data {
-        let query = Query::new();
+/// How long a notice stays in the status bar.
+pub const NOTICE: Duration = Duration::from_secs(5);
 
-        let artists = client.list(&Term::Tag(Borrowed("Artist")), &query);
-        let albums = client.list(&Term::Tag(Borrowed("Album")), &query);
-        let titles = client.list(&Term::Tag(Borrowed("Title")), &query);
-        let playlists = client.playlists().unwrap();
-        let queue = client.queue().unwrap();
+impl<'a> Data {
+    pub fn new(
+        client: &mut Client,
+        connection: &Connection,
+        colors: ColorScheme,
+        columns: &[Column],
+    ) -> Result<Data> {
+        let (library, artists, albums, titles) = libraries(client, &|| Ok(()))?;
+        let playlists = client.playlists()?;
+        let queue = client.queue()?;
 
-        let mut artistitems: Vec<String> = vec![];
-        let mut albumitems: Vec<String> = vec!["[Back]".into()];
-        let mut titleitems: Vec<String> = vec![];
-        let mut queueitems: Vec<String> = vec![];
-        let mut libraryitems = vec![];
         let mut playitems = vec![];
-
-        for mut artists in artists.unwrap() {
-            if artists.is_empty() {
-                artists = "[All Albums]".into()
-            }
-            artistitems.push(artists.clone());
-            libraryitems.push(artists);
-        }
-        //artistitems.remove(0);
-        for albums in albums.unwrap() {
-            albumitems.push(albums);
-        }
-        albumitems.remove(0);
-        for titles in titles.unwrap() {
-            titleitems.push(titles);
-        }
-        titleitems.remove(0);
-        for queue in queue {
-            let q = queue.title.unwrap_or("".into());
-            queueitems.push(q);
-        }
         for play in playlists {
             playitems.push(play.name);
         }
-
-        let library = Library::new(libraryitems, LibKind::Artist);
-        let artists = Library::new(artistitems, LibKind::Artist);
-        let albums = Library::new(albumitems, LibKind::Album);
-        let titles = Library::new(titleitems, LibKind::Title);
-        let queue = Library::new(queueitems, LibKind::None);
         let playlists = Library::new(playitems, LibKind::None);
 
         let tabindex: usize = 0;
-        let status = client.status().unwrap();
-        Data {
+        let status = client.status()?;
+        let modes = Modes::fetch(client)?;
+        let current = client.currentsong()?.unwrap_or_default();
+        let mut queue = Library::newlib(queue_items(queue, columns));
+        highlight(&mut queue.items, &current, colors);
+        Ok(Data {
             library,
             artists,
             albums,
@@ -80,29 +108,134 @@ impl<'a> Data {
             settings: Settings::new(),
             status,
             tabindex,
-            colors: ColorScheme {
-                foreground: Color::White,
-                background: Color::Black,
-                highlight: Color::Blue,
-            },
-            current: match client.currentsong() {
-                Ok(song) => match song {
-                    Some(song) => song,
-                    None => Song::default(),
-                },
-                Err(_io) => Song::default(),
-            },
-            style: tui::style::Style::default()
-                .fg(Color::White)
-                .bg(Color::Black),
+            wrap: false,
+            colors,
+            columns: columns.to_vec(),
+            current,
+            style: colors.style(),
+            options: false,
+            options_state: ListState::default(),
+            modes,
+            art: art::Cache::default(),
+            lyrics: lyrics::Pane::default(),
+            connection: connection.clone(),
+            offline: None,
+            search: None,
+            finder: None,
+            menu: None,
+            info: None,
+            help: None,
+            selection: Selection::default(),
+            playlist: None,
+            prompt: None,
+            ticked: Instant::now(),
+            requests: vec![],
+            busy: false,
+            areas: Areas::default(),
+            drag: None,
+            click: None,
+            notice: None,
+        })
+    }
+
+    /// An empty `Data` to draw while no server is reachable.
+    pub fn offline(offline: Offline, colors: ColorScheme, connection: Connection) -> Data {
+        Data {
+            library: Library::new(vec![], LibKind::Artist),
+            artists: Library::new(vec![], LibKind::Artist),
+            albums: Library::new(vec![], LibKind::Album),
+            titles: Library::new(vec![], LibKind::Title),
+            playlists: Library::new(vec![], LibKind::None),
+            queue: Library::new(vec![], LibKind::None),
+            settings: Settings::new(),
+            status: Status::default(),
+            tabindex: 0,
+            wrap: false,
+            colors,
+            columns: vec![],
+            current: Song::default(),
+            style: colors.style(),
             options: false,
+            options_state: ListState::default(),
+            modes: Modes::default(),
+            art: art::Cache::default(),
+            lyrics: lyrics::Pane::default(),
+            connection,
+            offline: Some(offline),
+            search: None,
+            finder: None,
+            menu: None,
+            info: None,
+            help: None,
+            selection: Selection::default(),
+            playlist: None,
+            prompt: None,
+            ticked: Instant::now(),
+            requests: vec![],
+            busy: false,
+            areas: Areas::default(),
+            drag: None,
+            click: None,
+            notice: None,
         }
     }
 
-    pub fn update(&mut self, client: &mut mpd::Client) {
+    pub fn update(&mut self, client: &mut Client) -> Result<()> {
         let tabindex = self.tabindex;
-        *self = Self::new(client);
+        let wrap = self.wrap;
+        let art = mem::take(&mut self.art);
+        *self = Self::new(client, &self.connection, self.colors, &self.columns)?;
         self.tabindex = tabindex;
+        self.wrap = wrap;
+        self.art = art;
+        Ok(())
+    }
+
+    /// Swaps in library lists reloaded after the database changed. Searches
+    /// and the finder index refer to the old database and are dropped.
+    pub fn set_libraries(&mut self, libraries: Libraries) {
+        let (library, artists, albums, titles) = libraries;
+        self.library = library;
+        self.artists = artists;
+        self.albums = albums;
+        self.titles = titles;
+        self.search = None;
+        self.finder = None;
+    }
+
+    pub fn set_status(&mut self, status: Status) {
+        self.status = status;
+        self.ticked = Instant::now();
+    }
+
+    pub fn notify(&mut self, text: String) {
+        self.notice = Some((text, Instant::now()));
+    }
+
+    /// Moves the elapsed time on while playing, so the position keeps up
+    /// between status updates from the server.
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        if self
+            .notice
+            .as_ref()
+            .is_some_and(|(_, at)| now - *at >= NOTICE)
+        {
+            self.notice = None;
+        }
+        let passed = now - self.ticked;
+        self.ticked = now;
+        if self.status.state != State::Play {
+            return;
+        }
+        if let (Some(elapsed), Ok(passed)) = (self.status.elapsed, time::Duration::from_std(passed))
+        {
+            let elapsed = elapsed + passed;
+            self.status.elapsed = Some(match self.status.duration {
+                Some(duration) if elapsed > duration => duration,
+                _ => elapsed,
+            });
+        }
     }
 
     pub fn nexttab(&mut self) {
@@ -116,35 +249,58 @@ impl<'a> Data {
         }
     }
 
-    pub fn up(&mut self) {
-        match self.tabindex {
-            1 => self.library.previous(),
-            2 => self.albums.previous(),
-            3 => self.queue.previous(),
-            4 => self.settings.previous(),
-            _ => {}
+    /// Moves the cursor of the list in view.
+    pub fn motion(&mut self, motion: Motion) {
+        let wrap = self.wrap;
+        if self.options {
+            short_motion(&mut self.options_state, modes::ROWS.len(), motion, wrap);
+            return;
         }
-    }
-
-    pub fn down(&mut self) {
         match self.tabindex {
-            1 => self.library.next(),
-            2 => self.albums.next(),
-            3 => self.queue.next(),
-            4 => self.settings.next(),
+            1 => self.library.motion(motion, wrap),
+            2 => self.playlists.motion(motion, wrap),
+            3 => self.queue.motion(motion, wrap),
+            4 => self.settings.motion(motion, wrap),
             _ => {}
         }
     }
 }
 
+/// Moves the cursor of a `List` short enough to be drawn whole, so it moves
+/// the same as a `Tree`.
+fn short_motion(state: &mut ListState, len: usize, motion: Motion, wrap: bool) {
+    let mut whole = LibState::default();
+    whole.select(state.selected());
+    whole.height(len);
+    whole.motion(motion, len, wrap);
+    state.select(whole.selected());
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ColorScheme {
+    #[serde(deserialize_with = "config::color")]
     pub foreground: Color,
+    #[serde(deserialize_with = "config::color")]
     pub background: Color,
+    #[serde(deserialize_with = "config::color")]
     pub highlight: Color,
 }
 
-pub struct ArtistOptions {
-    pub items: Vec<String>,
+impl Default for ColorScheme {
+    fn default() -> ColorScheme {
+        ColorScheme {
+            foreground: Color::White,
+            background: Color::Black,
+            highlight: Color::Blue,
+        }
+    }
+}
+
+impl ColorScheme {
+    pub fn style(&self) -> Style {
+        Style::default().fg(self.foreground).bg(self.background)
+    }
 }
 
 #[derive(Clone)]
@@ -164,27 +320,47 @@ impl<'a> Library {
         }
     }
 
-    pub fn add_to_queue(&self, data: &Data, client: &mut mpd::Client) {
-        let mut query = Query::new();
-        let index = data.library.state.selected().unwrap();
-        match self.items[index].tag {
-            LibKind::Artist => {
-                let query = query.and(
-                    Term::Tag("Artist".into()),
-                    self.items[index].content.clone(),
-                );
-                client.findadd(&query).unwrap();
-            }
+    pub fn add_to_queue(&self, data: &Data, client: &mut Client) -> Result<()> {
+        let index = data.library.state.selected().unwrap_or(0);
+        if let Some(SongRef::File(file)) = self.items.get(index).map(|i| &i.song) {
+            client.push(song(file))?;
+        } else if let Some(terms) = self.terms(index) {
+            with_query(&terms, |query| client.findadd(query))?;
+        }
+        Ok(())
+    }
+
+    /// The songs behind the selected row.
+    pub fn songs(&self, client: &mut Client) -> Result<Vec<Song>> {
+        let index = self.state.selected().unwrap_or(0);
+        if let Some(SongRef::File(file)) = self.items.get(index).map(|i| &i.song) {
+            return Ok(vec![song(file)]);
+        }
+        match self.terms(index) {
+            Some(terms) => with_query(&terms, |query| client.find(query, None)),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Tags to search by for the songs under an artist or album row.
+    pub fn terms(&self, index: usize) -> Option<Vec<(&'static str, String)>> {
+        let item = self.items.get(index)?;
+        match item.tag {
+            LibKind::Artist => Some(vec![("Artist", item.content.clone())]),
             LibKind::Album => {
-                let query = query.and(Term::Tag("Album".into()), self.items[index].content.clone());
-                client.findadd(&query).unwrap();
-            }
-            LibKind::Title => {
-                let query = query.and(Term::Tag("Title".into()), self.items[index].content.clone());
-                client.findadd(&query).unwrap();
+                let mut terms = vec![("Album", item.content.clone())];
+                // The same album name can belong to several artists.
+                let artist = self.items[..index]
+                    .iter()
+                    .rev()
+                    .find(|i| i.tag == LibKind::Artist);
+                if let Some(artist) = artist {
+                    terms.push(("Artist", artist.content.clone()));
+                }
+                Some(terms)
             }
-            _ => {}
-        };
+            _ => None,
+        }
     }
 
     pub fn select_last(&mut self) {
@@ -202,7 +378,7 @@ impl<'a> Library {
         }
     }
 
-    pub fn get_albums(&mut self, client: &mut mpd::Client) -> Library {
+    pub fn get_albums(&mut self, client: &mut Client) -> Result<Library> {
         let idx: String = self.items[self.state.selected().unwrap()].content.clone();
         let mut query = Query::new();
 
@@ -212,17 +388,23 @@ impl<'a> Library {
             &query.and(Term::Tag("Artist".into()), Borrowed(name)),
         );
         let mut artistalbums: Vec<String> = vec![];
-        for albums in items.unwrap() {
+        for albums in items? {
             //albums.insert_str(0, "");
             artistalbums.push(albums);
         }
 
-        Library::new(artistalbums, LibKind::Album)
+        Ok(Library::new(artistalbums, LibKind::Album))
     }
 
-    pub fn get_titles(&mut self, client: &mut mpd::Client) -> Library {
-        let idx: String = self.items[self.state.selected().unwrap()].content.clone();
-        let artist = self.items[1].content.clone();
+    pub fn get_titles(&mut self, client: &mut Client) -> Result<Library> {
+        let selected = self.state.selected().unwrap();
+        let idx: String = self.items[selected].content.clone();
+        let artist = self.items[..selected]
+            .iter()
+            .rev()
+            .find(|i| i.tag == LibKind::Artist)
+            .map(|i| i.content.clone())
+            .unwrap_or_default();
 
         let name: &str = idx.as_str();
 
@@ -230,19 +412,15 @@ impl<'a> Library {
         let query = query
             .and(Term::Tag("Album".into()), Borrowed(name))
             .and(Term::Tag("Artist".into()), artist);
-        let mut items = client.search(&query, None).unwrap();
-        items.sort_by_key(|song| {
-            song.tags
-                .get("Track")
-                .cloned()
-                .map(|t| t.parse::<u32>().unwrap())
-        });
-        let mut albumtitles: Vec<String> = vec![];
-        for albums in items {
-            albumtitles.push(albums.title.unwrap_or("".into()));
+        let mut items = client.search(&query, None)?;
+        items.sort_by_key(track);
+        let mut albumtitles = vec![];
+        for song in items {
+            let title = song.title.unwrap_or("".into());
+            albumtitles.push(LibItem::new(title, LibKind::Title).song(SongRef::File(song.file)));
         }
 
-        Library::new(albumtitles, LibKind::Title)
+        Ok(Library::newlib(albumtitles))
     }
 
     /*pub fn enter(&mut self, data: &mut Data) -> Library {
@@ -257,30 +435,15 @@ impl<'a> Library {
     }*/
 
     pub fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i >= self.items.len() - 1 {
-                    self.items.len() - 1
-                } else {
-                    i + 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
+        self.state.motion(Motion::Down(1), self.items.len(), false);
     }
+
     pub fn previous(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    0
-                } else {
-                    i - 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
+        self.state.motion(Motion::Up(1), self.items.len(), false);
+    }
+
+    pub fn motion(&mut self, motion: Motion, wrap: bool) {
+        self.state.motion(motion, self.items.len(), wrap);
     }
 }
 
@@ -300,7 +463,6 @@ impl FromIterator<LibItem> for Library {
 pub struct Artists {
     pub items: Vec<LibItem>,
     pub state: LibState,
-    pub options: Vec<String>,
 }
 
 impl<'a> Artists {
@@ -311,11 +473,10 @@ impl<'a> Artists {
                 .iter()
                 .map(|i| LibItem::new(i.clone(), LibKind::Artist))
                 .collect(),
-            options: vec![String::from("add to queue")],
         }
     }
 
-    pub fn update(&mut self, client: &mut mpd::Client) {
+    pub fn update(&mut self, client: &mut Client) {
         let query = Query::new();
 
         let artists = client.list(&Term::Tag(Borrowed("Artist")), &query);
@@ -328,7 +489,7 @@ impl<'a> Artists {
         self.items = items
     }
 
-    pub fn get_albums(&mut self, client: &mut mpd::Client) -> Albums {
+    pub fn get_albums(&mut self, client: &mut Client) -> Albums {
         let idx: String = self.items[self.state.selected().unwrap()].content.clone();
         let mut query = Query::new();
 
@@ -349,33 +510,12 @@ impl<'a> Artists {
         //Albums::new(self.items[self.state.selected()]);
     }
 
-    pub fn options(&self) {}
-
     pub fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i >= self.items.len() - 1 {
-                    0
-                } else {
-                    i + 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
+        self.state.motion(Motion::Down(1), self.items.len(), false);
     }
+
     pub fn previous(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.items.len() - 1
-                } else {
-                    i - 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
+        self.state.motion(Motion::Up(1), self.items.len(), false);
     }
 }
 
@@ -395,7 +535,7 @@ impl<'a> Albums {
         }
     }
 
-    pub fn update(&mut self, client: &mut mpd::Client) {
+    pub fn update(&mut self, client: &mut Client) {
         let query = Query::new();
 
         let albums = client.list(&Term::Tag(Borrowed("Album")), &query);
@@ -406,7 +546,7 @@ impl<'a> Albums {
         }
     }
 
-    pub fn get_titles(&mut self, client: &mut mpd::Client, library: &Library) -> Titles {
+    pub fn get_titles(&mut self, client: &mut Client, library: &Library) -> Titles {
         let idx: String = library.items[library.state.selected().unwrap()]
             .content
             .clone();
@@ -419,12 +559,7 @@ impl<'a> Albums {
             .and(Term::Tag("Album".into()), Borrowed(name))
             .and(Term::Tag("Artist".into()), artist);
         let mut items = client.search(&query, None).unwrap();
-        items.sort_by_key(|song| {
-            song.tags
-                .get("Track")
-                .cloned()
-                .map(|t| t.parse::<u32>().unwrap())
-        });
+        items.sort_by_key(track);
         let mut albumtitles: Vec<String> = vec![];
         for albums in items {
             albumtitles.push(albums.title.unwrap_or("".into()));
@@ -434,30 +569,11 @@ impl<'a> Albums {
     }
 
     pub fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i >= self.items.len() - 1 {
-                    0
-                } else {
-                    i + 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
+        short_motion(&mut self.state, self.items.len(), Motion::Down(1), false);
     }
+
     pub fn previous(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.items.len() - 1
-                } else {
-                    i - 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
+        short_motion(&mut self.state, self.items.len(), Motion::Up(1), false);
     }
 }
 
@@ -477,7 +593,7 @@ impl<'a> Titles {
         }
     }
 
-    pub fn update(&mut self, client: &mut mpd::Client) {
+    pub fn update(&mut self, client: &mut Client) {
         let query = Query::new();
 
         let titles = client.list(&Term::Tag(Borrowed("Title")), &query);
@@ -490,46 +606,138 @@ impl<'a> Titles {
     }
 
     pub fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i >= self.items.len() - 1 {
-                    0
-                } else {
-                    i + 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
+        short_motion(&mut self.state, self.items.len(), Motion::Down(1), false);
     }
+
     pub fn previous(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.items.len() - 1
-                } else {
-                    i - 1
-                }
+        short_motion(&mut self.state, self.items.len(), Motion::Up(1), false);
+    }
+}
+
+pub fn update_queue(data: &mut Data, client: &mut Client) -> Result<()> {
+    let queue = client.queue()?;
+    let current = client.currentsong()?.unwrap_or(Song::default());
+    let cursor = data.queue.state.selected();
+    let id = cursor
+        .and_then(|row| data.queue.items.get(row))
+        .and_then(|item| item.song.id());
+    data.queue.items = queue_items(queue, &data.columns);
+    highlight(&mut data.queue.items, &current, data.colors);
+
+    // Keep the cursor on the same song when it moved, or on the same row
+    // when it is gone.
+    let len = data.queue.items.len();
+    let row = id
+        .and_then(|id| {
+            data.queue
+                .items
+                .iter()
+                .position(|item| item.song.id() == Some(id))
+        })
+        .or_else(|| cursor.map(|row| row.min(len.saturating_sub(1))));
+    data.queue.state.select(row.or(Some(0)));
+    data.selection.retain(&data.queue.items);
+    Ok(())
+}
+
+/// Rows for the queue tab, each pointing at its queue entry.
+fn queue_items(queue: Vec<Song>, columns: &[Column]) -> Vec<LibItem> {
+    queue
+        .into_iter()
+        .map(|song| {
+            let mut item = LibItem::new(Column::Title.cell(&song), LibKind::None);
+            item.cells = queue::cells(columns, &song);
+            if let Some(place) = song.place {
+                item.song = SongRef::Queue {
+                    id: place.id,
+                    pos: place.pos,
+                };
             }
-            None => 0,
+            item
+        })
+        .collect()
+}
+
+/// The track number of `song`, from a tag such as `3` or `3/12`. Songs
+/// without one, or with one that is not a number, sort first.
+fn track(song: &Song) -> Option<u32> {
+    let tag = song.tags.get("Track")?;
+    tag.split('/').next().and_then(|n| n.trim().parse().ok())
+}
+
+/// Highlights the playing song among the queue rows.
+pub fn highlight(items: &mut [LibItem], current: &Song, colors: ColorScheme) {
+    let playing = current.place.map(|place| place.id);
+    for item in items {
+        item.style = if playing.is_some() && item.song.id() == playing {
+            Style::default()
+                .fg(Color::Rgb(45, 78, 32))
+                .bg(colors.background)
+        } else {
+            Style::default()
         };
-        self.state.select(Some(i));
     }
 }
 
-pub fn update_queue(data: &mut Data, client: &mut mpd::Client) {
-    let queue = client.queue().unwrap();
-    let current = client.currentsong().unwrap().unwrap_or(Song::default());
-    let mut items = vec![];
-    for song in queue {
-        items.push(LibItem::new(song.title.unwrap_or("".into()), LibKind::None))
+/// The library lists: the artists shown at the top level, then all artists,
+/// albums and titles.
+pub type Libraries = (Library, Library, Library, Library);
+
+pub fn libraries(client: &mut Client, check: Check) -> Result<Libraries> {
+    let query = Query::new();
+
+    let artists = client.list(&Term::Tag(Borrowed("Artist")), &query);
+    check()?;
+    let albums = client.list(&Term::Tag(Borrowed("Album")), &query);
+    check()?;
+    let titles = client.list(&Term::Tag(Borrowed("Title")), &query);
+
+    let mut artistitems: Vec<String> = vec![];
+    let mut albumitems: Vec<String> = vec!["[Back]".into()];
+    let mut titleitems: Vec<String> = vec![];
+    let mut libraryitems = vec![];
+
+    for mut artists in artists? {
+        if artists.is_empty() {
+            artists = "[All Albums]".into()
+        }
+        artistitems.push(artists.clone());
+        libraryitems.push(artists);
     }
-    data.queue.items = items;
-    if data.queue.items.len() > 0 {
-        data.queue.items[current.place.unwrap_or(QueuePlace::default()).pos as usize].style =
-            Style::default()
-                .fg(Color::Rgb(45, 78, 32))
-                .bg(data.colors.background);
+    //artistitems.remove(0);
+    for albums in albums? {
+        albumitems.push(albums);
+    }
+    albumitems.remove(0);
+    for titles in titles? {
+        titleitems.push(titles);
+    }
+    if !titleitems.is_empty() {
+        titleitems.remove(0);
+    }
+
+    Ok((
+        Library::new(libraryitems, LibKind::Artist),
+        Library::new(artistitems, LibKind::Artist),
+        Library::new(albumitems, LibKind::Album),
+        Library::new(titleitems, LibKind::Title),
+    ))
+}
+
+pub fn with_query<T>(terms: &[(&'static str, String)], f: impl FnOnce(&Query) -> T) -> T {
+    let mut query = Query::new();
+    let mut q = &mut query;
+    for (tag, value) in terms {
+        q = q.and(Term::Tag(Borrowed(tag)), value.as_str());
+    }
+    f(q)
+}
+
+/// A song known only by its file, enough for the commands that take a song.
+pub fn song(file: &str) -> Song {
+    Song {
+        file: file.to_string(),
+        ..Song::default()
     }
 }
 
@@ -546,7 +754,7 @@ impl<'a> Queue {
         }
     }
 
-    pub fn update(&mut self, client: &mut mpd::Client) {
+    pub fn update(&mut self, client: &mut Client) {
         let queue = client.queue().unwrap();
         let mut items: Vec<String> = vec![];
         for queue in queue {
@@ -559,30 +767,11 @@ impl<'a> Queue {
     }
 
     pub fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i >= self.items.len() - 1 {
-                    0
-                } else {
-                    i + 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
+        short_motion(&mut self.state, self.items.len(), Motion::Down(1), false);
     }
+
     pub fn previous(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.items.len() - 1
-                } else {
-                    i - 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
+        short_motion(&mut self.state, self.items.len(), Motion::Up(1), false);
     }
 }
 
@@ -601,29 +790,14 @@ impl<'a> Settings {
     }
 
     pub fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i >= self.items.len() - 1 {
-                    0
-                } else {
-                    i + 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
+        self.motion(Motion::Down(1), false);
     }
+
     pub fn previous(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.items.len() - 1
-                } else {
-                    i - 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
+        self.motion(Motion::Up(1), false);
+    }
+
+    pub fn motion(&mut self, motion: Motion, wrap: bool) {
+        short_motion(&mut self.state, self.items.len(), motion, wrap);
     }
 }
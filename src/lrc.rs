@@ -0,0 +1,258 @@
+//! Lyrics files: LRC, with a time for every line, or plain text.
+
+/// One line of lyrics.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    /// When the line starts, in milliseconds, for timed lyrics.
+    pub time: Option<u64>,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Lyrics {
+    /// In order of time when the lyrics are timed, as in the file otherwise.
+    pub lines: Vec<Line>,
+}
+
+impl Lyrics {
+    pub fn timed(&self) -> bool {
+        self.lines.first().is_some_and(|l| l.time.is_some())
+    }
+
+    /// The line being sung `elapsed` milliseconds into the song. None before
+    /// the first line, and for lyrics without times.
+    pub fn current(&self, elapsed: u64) -> Option<usize> {
+        if !self.timed() {
+            return None;
+        }
+        let sung = self
+            .lines
+            .partition_point(|l| l.time.is_some_and(|t| t <= elapsed));
+        sung.checked_sub(1)
+    }
+}
+
+/// Reads LRC or plain text. A line of LRC starts with one or more `[mm:ss.xx]`
+/// times, and is sung at each of them. Tags such as `[ar:...]` are skipped,
+/// except `[offset:...]`, the milliseconds to show every line sooner by.
+/// Untimed lines are dropped from a file that has times, as are times too
+/// large to count in milliseconds.
+pub fn parse(text: &str) -> Lyrics {
+    let mut lines = vec![];
+    let mut offset = 0;
+    for line in text.trim_start_matches('\u{feff}').lines() {
+        let mut rest = line.trim_end_matches('\r');
+        let mut times = vec![];
+        let mut tagged = false;
+        while let Some((inside, after)) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+            if let Some(time) = timestamp(inside) {
+                times.push(time);
+            } else if let Some((key, value)) = inside.split_once(':') {
+                if key.trim().eq_ignore_ascii_case("offset") {
+                    offset = value.trim().parse().unwrap_or(0);
+                }
+                tagged = true;
+            } else {
+                break;
+            }
+            rest = after;
+        }
+        if times.is_empty() && tagged && rest.trim().is_empty() {
+            continue;
+        }
+        let text = words(rest).trim().to_string();
+        if times.is_empty() {
+            lines.push(Line { time: None, text });
+            continue;
+        }
+        for time in times {
+            lines.push(Line {
+                time: Some(time),
+                text: text.clone(),
+            });
+        }
+    }
+
+    if lines.iter().any(|l| l.time.is_some()) {
+        // Lines the offset pushes past the end of time are dropped.
+        lines.retain_mut(|l| match l.time {
+            Some(time) => {
+                l.time = shift(time, offset);
+                l.time.is_some()
+            }
+            None => false,
+        });
+        lines.sort_by_key(|l| l.time);
+    } else {
+        // Plain text keeps its blank lines, but not ones at the ends.
+        while lines.last().is_some_and(|l| l.text.is_empty()) {
+            lines.pop();
+        }
+        let start = lines.iter().take_while(|l| l.text.is_empty()).count();
+        lines.drain(..start);
+    }
+    Lyrics { lines }
+}
+
+/// `time` shown `offset` milliseconds sooner, but not before the start.
+fn shift(time: u64, offset: i64) -> Option<u64> {
+    if offset >= 0 {
+        Some(time.saturating_sub(offset.unsigned_abs()))
+    } else {
+        time.checked_add(offset.unsigned_abs())
+    }
+}
+
+/// Milliseconds in `mm:ss`, `mm:ss.x` to `mm:ss.xxx`, or `mm:ss:xx`.
+fn timestamp(s: &str) -> Option<u64> {
+    let (minutes, rest) = s.split_once(':')?;
+    let (seconds, fraction) = match rest.find(['.', ':']) {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, ""),
+    };
+    let number = |s: &str| {
+        if s.is_empty() || !s.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        s.parse::<u64>().ok()
+    };
+    let millis = match fraction.len() {
+        0 => 0,
+        1..=3 => number(fraction)? * 10u64.pow(3 - fraction.len() as u32),
+        _ => return None,
+    };
+    let seconds = number(seconds)?;
+    if seconds >= 60 {
+        return None;
+    }
+    number(minutes)?
+        .checked_mul(60)?
+        .checked_add(seconds)?
+        .checked_mul(1000)?
+        .checked_add(millis)
+}
+
+/// Strips the word times of enhanced LRC, such as `<00:12.30>`.
+fn words(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        match rest[start..].find('>') {
+            Some(end) if timestamp(&rest[start + 1..start + end]).is_some() => {
+                out.push_str(&rest[..start]);
+                rest = &rest[start + end + 1..];
+            }
+            _ => {
+                out.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times(lyrics: &Lyrics) -> Vec<(Option<u64>, &str)> {
+        lyrics
+            .lines
+            .iter()
+            .map(|l| (l.time, l.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp("00:00"), Some(0));
+        assert_eq!(timestamp("01:02"), Some(62_000));
+        assert_eq!(timestamp("01:02.3"), Some(62_300));
+        assert_eq!(timestamp("01:02.34"), Some(62_340));
+        assert_eq!(timestamp("01:02.345"), Some(62_345));
+        assert_eq!(timestamp("01:02:34"), Some(62_340));
+        assert_eq!(timestamp("75:00.00"), Some(4_500_000));
+        assert_eq!(timestamp("01:60.00"), None);
+        assert_eq!(timestamp("ar:Someone"), None);
+        assert_eq!(timestamp("01:02.3456"), None);
+        assert_eq!(timestamp("1:-2"), None);
+        assert_eq!(timestamp("307445734561825861:00"), None);
+        assert_eq!(timestamp("99999999999999999999:00"), None);
+    }
+
+    #[test]
+    fn timed_lines() {
+        let lyrics = parse("[ar:Artist]\n[ti:Title]\n[00:01.00]One\n[00:02.50] Two \n[00:04.00]\n");
+        assert_eq!(
+            times(&lyrics),
+            vec![(Some(1000), "One"), (Some(2500), "Two"), (Some(4000), "")]
+        );
+        assert!(lyrics.timed());
+    }
+
+    #[test]
+    fn repeated_lines_are_sorted() {
+        let lyrics = parse("[00:10.00][00:30.00]Chorus\n[00:20.00]Verse");
+        assert_eq!(
+            times(&lyrics),
+            vec![
+                (Some(10_000), "Chorus"),
+                (Some(20_000), "Verse"),
+                (Some(30_000), "Chorus")
+            ]
+        );
+    }
+
+    #[test]
+    fn offsets() {
+        let sooner = parse("[offset:+500]\n[00:01.00]One\n[00:00.20]Zero");
+        assert_eq!(times(&sooner), vec![(Some(0), "Zero"), (Some(500), "One")]);
+        let later = parse("[00:01.00]One\n[offset:-250]");
+        assert_eq!(times(&later), vec![(Some(1250), "One")]);
+        let broken = parse("[offset:soon]\n[00:01.00]One");
+        assert_eq!(times(&broken), vec![(Some(1000), "One")]);
+        let huge = parse("[offset:-9223372036854775808]\n[00:01.00]One");
+        assert_eq!(times(&huge), vec![(Some(9_223_372_036_854_776_808), "One")]);
+        let past = parse("[offset:9223372036854775807]\n[00:01.00]One");
+        assert_eq!(times(&past), vec![(Some(0), "One")]);
+    }
+
+    #[test]
+    fn overflowing_times_are_ignored() {
+        let lyrics = parse("[00:01.00]One\n[307445734561825861:00.00]Never\n[00:02.00]Two");
+        assert_eq!(
+            times(&lyrics),
+            vec![(Some(1000), "One"), (Some(2000), "Two")]
+        );
+        let late =
+            parse("[offset:-9223372036854775808]\n[307445734561825:00.00]Late\n[00:01.00]One");
+        assert_eq!(times(&late), vec![(Some(9_223_372_036_854_776_808), "One")]);
+    }
+
+    #[test]
+    fn word_times() {
+        let lyrics = parse("[00:01.00]<00:01.00>One <00:01.50>two <b>");
+        assert_eq!(times(&lyrics), vec![(Some(1000), "One two <b>")]);
+    }
+
+    #[test]
+    fn plain_text() {
+        let lyrics = parse("\u{feff}\nOne\r\n\nTwo [live]\n\n");
+        assert_eq!(
+            times(&lyrics),
+            vec![(None, "One"), (None, ""), (None, "Two [live]")]
+        );
+        assert!(!lyrics.timed());
+        assert_eq!(lyrics.current(10_000), None);
+    }
+
+    #[test]
+    fn current_line() {
+        let lyrics = parse("[00:01.00]One\n[00:02.00]Two\n[00:03.00]Three");
+        assert_eq!(lyrics.current(0), None);
+        assert_eq!(lyrics.current(1000), Some(0));
+        assert_eq!(lyrics.current(2999), Some(1));
+        assert_eq!(lyrics.current(60_000), Some(2));
+    }
+}
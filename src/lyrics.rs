@@ -0,0 +1,78 @@
+use crate::config::Directories;
+use crate::connection::Connection;
+use crate::libs::Data;
+use crate::lrc::{self, Lyrics};
+use crate::worker::Request;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What the worker needs to find the lyrics of one song.
+pub struct Lookup {
+    pub file: String,
+    pub artist: Option<String>,
+    pub title: Option<String>,
+}
+
+/// Lyrics of the song playing.
+#[derive(Default)]
+pub struct Pane {
+    /// The song they belong to, which may still be loading them.
+    pub file: String,
+    pub lyrics: Option<Lyrics>,
+}
+
+/// Asks the worker for the lyrics of the song playing, unless they are loaded
+/// or on their way.
+pub fn want(data: &mut Data) {
+    if data.current.file.is_empty() || data.lyrics.file == data.current.file {
+        return;
+    }
+    data.lyrics = Pane {
+        file: data.current.file.clone(),
+        lyrics: None,
+    };
+    data.requests.push(Request::Lyrics(Lookup {
+        file: data.current.file.clone(),
+        artist: data.current.tags.get("Artist").cloned(),
+        title: data.current.title.clone(),
+    }));
+}
+
+pub fn loaded(data: &mut Data, file: String, lyrics: Option<Lyrics>) {
+    if data.lyrics.file == file {
+        data.lyrics.lyrics = lyrics;
+    }
+}
+
+/// Reads the first lyrics file found: next to the song, then in the lyrics
+/// directory by artist and title, then there by the song's file name. LRC
+/// comes before plain text in each place.
+pub fn fetch(
+    connection: &Connection,
+    directories: &Directories,
+    lookup: &Lookup,
+) -> Option<Lyrics> {
+    let stem = Path::new(&lookup.file).file_stem()?.to_str()?;
+    let mut candidates: Vec<PathBuf> = vec![];
+    if let Some(dir) = directories.beside(connection, &lookup.file) {
+        candidates.push(dir.join(stem));
+    }
+    if let Some(dir) = &directories.lyrics {
+        if let (Some(artist), Some(title)) = (&lookup.artist, &lookup.title) {
+            // Slashes would make a path of it.
+            let name = format!("{} - {}", artist, title).replace('/', "_");
+            candidates.push(dir.join(name));
+        }
+        candidates.push(dir.join(stem));
+    }
+    candidates.iter().find_map(|base| {
+        ["lrc", "txt"].iter().find_map(|extension| {
+            let mut path = base.clone().into_os_string();
+            path.push(".");
+            path.push(extension);
+            let bytes = fs::read(path).ok()?;
+            Some(lrc::parse(&String::from_utf8_lossy(&bytes)))
+        })
+    })
+}
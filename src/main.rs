@@ -1,192 +1,391 @@
+mod art;
+mod cli;
+mod config;
+mod connection;
+mod control;
+// Parts of it are only used for MPRIS.
+mod dispatch;
 mod eve;
+mod finder;
+mod fuzzy;
+mod graphics;
+mod help;
+mod idle;
+mod image;
+mod info;
+mod ipc;
+mod keys;
 mod library;
 #[allow(dead_code)]
 mod libs;
+mod lrc;
+mod lyrics;
+mod menu;
+mod modes;
+mod mouse;
+#[cfg(feature = "mpris")]
+mod mpris;
+mod notifications;
+mod playlists;
 mod position;
+mod prompt;
+mod queue;
+mod search;
+mod seek;
 mod ui;
+mod worker;
 
+use cli::Args;
+use config::{Config, Directories, OPTIONS};
+use connection::{Client, Offline, Settings, Target};
 use eve::{Event, Events};
-use library::{LibItem, LibKind};
+use graphics::Graphics;
+use help::Help;
+use ipc::Message;
+use keys::{Action, Keymap, Resolved};
 use libs::{update_queue, Data};
+use modes::Modes;
+use mpd::idle::Subsystem;
+use notifications::Notifications;
+use worker::{Request, Worker};
 
-use mpd::{song::Song, status, Client, Query, Term};
-use std::borrow::Cow::Borrowed;
+use std::env;
 use std::error::Error;
 use std::io;
-use termion::{event::Key, raw::IntoRawMode};
-use tui::{backend::TermionBackend, Terminal};
+use std::mem;
+use std::process;
+use std::time::{Duration, Instant};
+use termion::input::MouseTerminal;
+use termion::raw::IntoRawMode;
+use tui::{backend::TermionBackend, layout::Rect, Terminal};
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::connect("127.0.0.1:6600").unwrap();
+/// How often the client pings mpd. mpd drops clients that stay quiet for its
+/// `connection_timeout`, a minute by default, and the client says nothing
+/// while the user does nothing.
+const KEEPALIVE: Duration = Duration::from_secs(30);
 
-    let events = Events::new();
+fn main() {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("flux: {}", err);
+            process::exit(control::USAGE);
+        }
+    };
+    match run(args) {
+        Ok(status) => process::exit(status),
+        Err(err) => {
+            eprintln!("flux: {}", err);
+            process::exit(control::FAILED);
+        }
+    }
+}
+
+/// Runs the command given, or the interface, and returns the exit status.
+fn run(mut args: Args) -> Result<i32, Box<dyn Error>> {
+    if args.help {
+        println!("{}", cli::USAGE);
+        return Ok(0);
+    }
+    let config = Config::load(args.config.as_deref())?;
+    let connection = args
+        .connection
+        .or(Settings::from_env())
+        .or(config.connection)
+        .connection();
+    if let Some(command) = args.command.take() {
+        return Ok(control::run(command, &connection));
+    }
+    let mut keymap = Keymap::new(&config.keys);
+    let mut events = Events::with_config(eve::Config {
+        tick_rate: config.tick_rate,
+        ..eve::Config::default()
+    });
+    events.disable_exit_key();
+    idle::watch(connection.clone(), events.sender());
+    let directories = Directories {
+        music: config.music_directory,
+        lyrics: config.lyrics_directory,
+    };
+    // Removes the socket again on the way out.
+    let _listener = ipc::listen(events.sender());
+    #[cfg(feature = "mpris")]
+    let mpris = mpris::start(events.sender());
+    let mut worker = Worker::spawn(connection.clone(), directories, events.sender());
 
-    let stdout = io::stdout().into_raw_mode()?;
+    let stdout = MouseTerminal::from(io::stdout().into_raw_mode()?);
     let backend = TermionBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
-    let mut data = Data::new(&mut client);
-
-    loop {
-        data.status = client.status()?;
-        data.current = match client.currentsong() {
-            Ok(song) => match song {
-                Some(song) => song,
-                None => Song::default(),
-            },
-            Err(_io) => Song::default(),
-        };
+    let mut graphics = Graphics::new(config.graphics, terminal.backend_mut())?;
+    let colors = config.colors;
+    let columns = config.queue.columns;
+    // Without mpd the interface opens offline and keeps trying to connect.
+    let (mut client, mut data) = match connection
+        .connect()
+        .and_then(|mut c| Data::new(&mut c, &connection, colors, &columns).map(|d| (c, d)))
+    {
+        Ok((c, data)) => (Some(c), data),
+        Err(err) => {
+            let offline = Offline::new(&connection.target, &err);
+            (None, Data::offline(offline, colors, connection.clone()))
+        }
+    };
+    data.tabindex = config.tab;
+    data.wrap = config.wrap;
+    data.art.pixels = graphics.pixels();
+    art::want(&mut data);
+    lyrics::want(&mut data);
+    let mut notifications = Notifications::new(config.notifications, &data);
+    let mut pinged = Instant::now();
 
-        if data.tabindex == 3 {
-            update_queue(&mut data, &mut client);
+    'main: loop {
+        if let Some(offline) = data.offline.as_mut().filter(|o| o.should_retry()) {
+            match connection
+                .connect()
+                .and_then(|mut c| Data::new(&mut c, &connection, colors, &columns).map(|d| (c, d)))
+            {
+                Ok((c, new)) => {
+                    let tabindex = data.tabindex;
+                    let wrap = data.wrap;
+                    let art = mem::take(&mut data.art);
+                    data = new;
+                    data.tabindex = tabindex;
+                    data.wrap = wrap;
+                    data.art = art;
+                    art::want(&mut data);
+                    lyrics::want(&mut data);
+                    client = Some(c);
+                }
+                Err(err) => offline.failed(&err),
+            }
         }
 
-        terminal.draw(|f| ui::draw(f, &mut data)).unwrap();
+        for request in data.requests.drain(..) {
+            worker.send(request);
+        }
+        data.busy = worker.busy();
+        notifications.update(&data);
+        #[cfg(feature = "mpris")]
+        if let Some(mpris) = &mpris {
+            mpris.update(&data);
+        }
+        terminal.draw(|f| ui::draw(f, &mut data))?;
+        if graphics.show(terminal.backend_mut(), cover(&data))? {
+            terminal.clear()?;
+            terminal.draw(|f| ui::draw(f, &mut data))?;
+            graphics.show(terminal.backend_mut(), cover(&data))?;
+        }
 
-        if let Event::Input(input) = events.next()? {
-            match input {
-                Key::Char('q') => {
-                    break;
-                }
-                Key::Char('o') => {
-                    if data.options {
-                        data.options = false
-                    } else {
-                        data.options = true
+        let input = match events.next()? {
+            Event::Input(input) => input,
+            Event::Mouse(mouse) => {
+                if let Some(c) = client.as_mut() {
+                    if let Err(err) = mouse::input(mouse, &mut data, c) {
+                        disconnected(&mut data, &mut client, &connection.target, &err);
                     }
                 }
-                Key::Char('1') => data.tabindex = 0,
-                Key::Char('2') => data.tabindex = 1,
-                Key::Char('3') => data.tabindex = 2,
-                Key::Char('4') => data.tabindex = 3,
-                Key::Char('5') => data.tabindex = 4,
-                Key::Char('s') => {
-                    client.stop()?;
+                continue;
+            }
+            Event::Tick => {
+                data.tick();
+                if pinged.elapsed() >= KEEPALIVE {
+                    pinged = Instant::now();
+                    if let Some(c) = client.as_mut() {
+                        if let Err(err) = c.ping() {
+                            disconnected(&mut data, &mut client, &connection.target, &err);
+                        }
+                    }
                 }
-                Key::Char('+') => {
-                    if data.status.volume < 99 {
-                        let vol = &data.status.volume + 2;
-                        client.volume(vol)?;
+                continue;
+            }
+            Event::Changed(subsystem) => {
+                if let Some(c) = client.as_mut() {
+                    if let Err(err) = refresh(&mut data, c, subsystem) {
+                        disconnected(&mut data, &mut client, &connection.target, &err);
                     }
                 }
-                Key::Char('-') => {
-                    if data.status.volume > 1 {
-                        let vol = &data.status.volume - 2;
-                        client.volume(vol)?;
+                continue;
+            }
+            Event::Done(kind, id, reply) => {
+                if worker.accept(kind, id) {
+                    worker::apply(kind, reply, &mut data);
+                }
+                continue;
+            }
+            Event::Remote(message, reply) => {
+                let result = match (message, client.as_mut()) {
+                    (Message::Action(Action::Quit), _) => {
+                        let _ = reply.send(Ok(()));
+                        break;
+                    }
+                    (Message::Action(Action::Help), _) => {
+                        let scope = scope(&data);
+                        Help::show(&mut data, &keymap, scope);
+                        Ok(())
+                    }
+                    (Message::Action(action), Some(c)) => match dispatch::run(action, &mut data, c)
+                    {
+                        Ok(()) => Ok(()),
+                        Err(err) => {
+                            disconnected(&mut data, &mut client, &connection.target, &err);
+                            Err(err.to_string())
+                        }
+                    },
+                    (Message::Action(_), None) => Err(String::from("not connected to mpd")),
+                    (message, _) => {
+                        ipc::apply(message, &mut data);
+                        Ok(())
+                    }
+                };
+                // The sender may have gone already.
+                let _ = reply.send(result);
+                continue;
+            }
+            #[cfg(feature = "mpris")]
+            Event::Mpris(command) => {
+                if let Some(c) = client.as_mut() {
+                    if let Err(err) = mpris::apply(command, &mut data, c) {
+                        disconnected(&mut data, &mut client, &connection.target, &err);
                     }
                 }
-                Key::Char('p') => {
-                    let status = client.status()?;
-                    if status.state == status::State::Play {
-                        client.pause(true)?
-                    } else {
-                        client.play()?;
+                continue;
+            }
+            Event::Attributes(attributes) => {
+                graphics.answered(&attributes);
+                data.art.pixels = graphics.pixels();
+                continue;
+            }
+            Event::Lost => {
+                // The watcher lost its connection; find out whether ours is
+                // gone as well.
+                if let Some(c) = client.as_mut() {
+                    if let Err(err) = c.ping() {
+                        disconnected(&mut data, &mut client, &connection.target, &err);
                     }
                 }
-                Key::Char('u') => {
-                    client.update().unwrap();
-                    data.update(&mut client);
+                continue;
+            }
+        };
+        if data.prompt.is_some() {
+            if let Some(c) = client.as_mut() {
+                if let Err(err) = prompt::input(input, &mut data, c) {
+                    disconnected(&mut data, &mut client, &connection.target, &err);
                 }
-                Key::Char('d') => {
-                    if data.tabindex == 3 {
-                        client
-                            .delete(data.queue.state.selected().unwrap_or(0) as u32)
-                            .unwrap_or(());
-                        update_queue(&mut data, &mut client);
-                        data.queue.select_last();
-                    }
+            }
+            continue;
+        }
+        if data.info.is_some() {
+            info::input(input, &mut data);
+            continue;
+        }
+        if data.help.is_some() {
+            help::input(input, &mut data);
+            continue;
+        }
+        if data.menu.is_some() {
+            if let Some(c) = client.as_mut() {
+                if let Err(err) = menu::input(input, &mut data, c) {
+                    disconnected(&mut data, &mut client, &connection.target, &err);
                 }
-                Key::Down => data.down(),
-                Key::Up => data.up(),
-                Key::Right => data.nexttab(),
-                Key::Left => data.prevtab(),
-                Key::Char('a') => data.library.add_to_queue(&data, &mut client),
-                Key::Char('.') => {
-                    client.next()?;
-                    client.pause(true)?;
-                    client.play()?
+            }
+            continue;
+        }
+        if data.finder.as_ref().is_some_and(|f| f.open) {
+            if let Some(c) = client.as_mut() {
+                if let Err(err) = finder::input(input, &mut data, c) {
+                    disconnected(&mut data, &mut client, &connection.target, &err);
                 }
-                Key::Char(',') => {
-                    client.prev()?;
-                    client.pause(true)?;
-                    client.play()?
+            }
+            continue;
+        }
+        if data.search.as_ref().is_some_and(|s| s.editing) {
+            search::input(input, &mut data);
+            continue;
+        }
+        if let Resolved::Action(action, count) = keymap.resolve(scope(&data), input) {
+            match action {
+                Action::Quit => break 'main,
+                Action::Help => {
+                    let scope = scope(&data);
+                    Help::show(&mut data, &keymap, scope);
                 }
-                Key::Char('\n') => {
-                    let idx = data.library.state.selected().unwrap();
-
-                    if data.tabindex == 3 {
-                        client.switch(data.queue.state.selected().unwrap() as u32)?;
-                        client.pause(true)?;
-                        client.play()?;
+                _ => {
+                    if let Some(c) = client.as_mut() {
+                        if let Err(err) = dispatch::counted(action, count, &mut data, c) {
+                            disconnected(&mut data, &mut client, &connection.target, &err);
+                        }
                     }
-                    if data.tabindex == 1 {
-                        if data.library.items[idx].tag == LibKind::Artist {
-                            let pos = data.library.state.selected().unwrap();
-                            data.artists.state.select(Some(pos));
-                            let item = data.library.get_albums(&mut client);
-                            data.albums.items.clear();
-                            data.library.state.select(Some(0));
-                            let mut temp = vec![
-                                LibItem::new("  [Back]".into(), LibKind::Back),
-                                LibItem::new(
-                                    data.library.items[pos].content.clone().into(),
-                                    LibKind::Artist,
-                                ),
-                            ];
-                            data.albums.items.append(&mut temp);
-                            for (i, album) in item.items.iter().enumerate() {
-                                data.albums.items.insert(
-                                    i + 2,
-                                    LibItem::new(album.content.clone(), LibKind::Album),
-                                );
-                                data.library = data.albums.clone();
-                            }
-                        } else if data.library.items[idx].tag == LibKind::Album {
-                            let pos = data.library.state.selected().unwrap();
-                            data.albums.state.select(Some(pos));
-                            let item = data.library.get_titles(&mut client);
-                            data.titles.items.clear();
-                            data.library.state.select(Some(0));
-                            let mut temp = vec![
-                                LibItem::new("  [Back]".into(), LibKind::Back),
-                                LibItem::new(
-                                    data.library.items[pos].content.clone().into(),
-                                    LibKind::Album,
-                                ),
-                            ];
-                            data.titles.items.append(&mut temp);
-                            for (i, album) in item.items.iter().enumerate() {
-                                data.titles.items.insert(
-                                    i + 2,
-                                    LibItem::new(album.content.clone(), LibKind::Title),
-                                );
-                            }
-                            data.library = data.titles.clone();
-                        } else if data.library.items[idx].tag == LibKind::Title {
-                            client
-                                .findadd(&Query::new().and(
-                                    Term::Tag(Borrowed("Title")),
-                                    Borrowed(data.library.items[idx].content.as_str()),
-                                ))
-                                .unwrap();
-                            update_queue(&mut data, &mut client);
-                            client.switch(data.queue.items.len() as u32 - 1)?;
-                            client.pause(true)?;
-                            client.play()?;
-                        } else if data.library.items[idx].tag == LibKind::Back {
-                            match data.library.items[idx + 1].tag {
-                                LibKind::Artist => {
-                                    data.library = data.artists.clone();
-                                    data.albums.state.select(Some(0))
-                                }
-                                LibKind::Album => data.library = data.albums.clone(),
-                                _ => {}
-                            }
-                        };
-                    };
                 }
-                _ => {}
             }
         }
     }
+    graphics.show(terminal.backend_mut(), None)?;
+    Ok(0)
+}
+
+/// Where key bindings are looked up: the tab shown, or the options pane, which
+/// has bindings of its own while it is open.
+fn scope(data: &Data) -> usize {
+    if data.options {
+        OPTIONS
+    } else {
+        data.tabindex
+    }
+}
+
+/// Where the cover art goes, if it is to be drawn in pixels, with the album
+/// it is of.
+fn cover(data: &Data) -> Option<(Rect, String, &image::Image)> {
+    let area = data.areas.cover?;
+    Some((area, art::key(&data.current), data.art.get(&data.current)?))
+}
+
+/// Brings the parts of `data` that depend on `subsystem` up to date.
+fn refresh(data: &mut Data, client: &mut Client, subsystem: Subsystem) -> mpd::error::Result<()> {
+    match subsystem {
+        Subsystem::Player => {
+            data.set_status(client.status()?);
+            data.current = client.currentsong()?.unwrap_or_default();
+            libs::highlight(&mut data.queue.items, &data.current, data.colors);
+            art::want(data);
+            lyrics::want(data);
+        }
+        Subsystem::Mixer => data.set_status(client.status()?),
+        Subsystem::Options => {
+            data.set_status(client.status()?);
+            data.modes = Modes::fetch(client)?;
+        }
+        Subsystem::Queue => {
+            data.set_status(client.status()?);
+            update_queue(data, client)?;
+        }
+        Subsystem::Playlist => playlists::reload(data, client)?,
+        Subsystem::Database => data.requests.push(Request::Library),
+        _ => {}
+    }
     Ok(())
 }
+
+/// Drops the client after an I/O or protocol error and switches to the offline
+/// screen. Errors reported by mpd for a single command leave the connection
+/// alone and go to the status bar.
+fn disconnected(
+    data: &mut Data,
+    client: &mut Option<Client>,
+    target: &Target,
+    err: &mpd::error::Error,
+) {
+    if !connection::is_disconnect(err) {
+        data.notify(err.to_string());
+        return;
+    }
+    *client = None;
+    let tabindex = data.tabindex;
+    let wrap = data.wrap;
+    let connection = data.connection.clone();
+    let art = mem::take(&mut data.art);
+    *data = Data::offline(Offline::new(target, err), data.colors, connection);
+    data.tabindex = tabindex;
+    data.wrap = wrap;
+    data.art = art;
+}
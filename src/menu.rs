@@ -0,0 +1,288 @@
+use crate::connection::Client;
+use crate::dispatch;
+use crate::info::Info;
+use crate::keys;
+use crate::library::{LibItem, LibKind, SongRef};
+use crate::libs::{self, update_queue, Data, Library};
+use crate::playlists;
+
+use mpd::{error::Result, Id, Song};
+use std::ops::Range;
+use termion::event::Key;
+
+/// Id and position of a queue row.
+type Place = (Id, u32);
+
+/// Something the context menu can do with the songs behind the row it was
+/// opened on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Choice {
+    PlayNow,
+    AddNext,
+    Append,
+    AddToPlaylist,
+    Info,
+    GoToArtist,
+    GoToAlbum,
+}
+
+impl Choice {
+    fn label(self) -> &'static str {
+        match self {
+            Choice::PlayNow => "Play now",
+            Choice::AddNext => "Play next",
+            Choice::Append => "Add to queue",
+            Choice::AddToPlaylist => "Add to playlist…",
+            Choice::Info => "Song info",
+            Choice::GoToArtist => "Go to artist",
+            Choice::GoToAlbum => "Go to album",
+        }
+    }
+}
+
+/// A command that queues all the songs of a row at once, so they need not
+/// be added one by one.
+enum Batch {
+    /// The songs of an artist or album row, by their tags.
+    Find(Vec<(&'static str, String)>),
+    /// A whole stored playlist, by its name.
+    Playlist(String),
+}
+
+/// The popup opened on the highlighted row of the library, playlists or
+/// queue tab.
+pub struct Menu {
+    pub list: Library,
+    choices: Vec<Choice>,
+    songs: Vec<Song>,
+    batch: Option<Batch>,
+    /// The row, when the menu was opened in the queue.
+    queued: Option<Place>,
+}
+
+impl Menu {
+    pub fn open(data: &mut Data, client: &mut Client) -> Result<()> {
+        let (songs, queued) = highlighted(data, client)?;
+        if songs.is_empty() {
+            return Ok(());
+        }
+
+        let mut choices = vec![Choice::PlayNow, Choice::AddNext];
+        if queued.is_none() {
+            choices.push(Choice::Append);
+        }
+        choices.push(Choice::AddToPlaylist);
+        if songs.len() == 1 {
+            choices.push(Choice::Info);
+        }
+        if shared(&songs, "Artist").is_some() {
+            choices.push(Choice::GoToArtist);
+            if shared(&songs, "Album").is_some() {
+                choices.push(Choice::GoToAlbum);
+            }
+        }
+        let items = choices
+            .iter()
+            .map(|c| LibItem::new(format!(" {}", c.label()), LibKind::None))
+            .collect();
+        data.menu = Some(Menu {
+            list: Library::newlib(items),
+            choices,
+            songs,
+            batch: batch(data),
+            queued,
+        });
+        Ok(())
+    }
+
+    /// Rows the popup needs, borders included.
+    pub fn height(&self) -> u16 {
+        self.choices.len() as u16 + 2
+    }
+}
+
+/// The songs behind the highlighted row of the library, playlists or queue
+/// tab, and the row itself when it is in the queue.
+pub fn highlighted(data: &Data, client: &mut Client) -> Result<(Vec<Song>, Option<Place>)> {
+    let (mut songs, queued) = match data.tabindex {
+        1 => (data.library.songs(client)?, None),
+        2 => (playlists::songs(data, client)?, None),
+        3 => match queue_row(data) {
+            Some((id, pos)) => (client.songs(id)?, Some((id, pos))),
+            None => (vec![], None),
+        },
+        _ => (vec![], None),
+    };
+    // Songs picked by file only carry their path.
+    if songs.len() == 1 && songs[0].tags.is_empty() {
+        if let Ok(song) = client.lsinfo(&songs[0]) {
+            songs[0] = song;
+        }
+    }
+    Ok((songs, queued))
+}
+
+fn batch(data: &Data) -> Option<Batch> {
+    match data.tabindex {
+        1 => {
+            let row = data.library.state.selected().unwrap_or(0);
+            data.library.terms(row).map(Batch::Find)
+        }
+        2 if data.playlist.is_none() => playlists::selected(data).map(Batch::Playlist),
+        _ => None,
+    }
+}
+
+fn queue_row(data: &Data) -> Option<Place> {
+    let row = data.queue.state.selected()?;
+    match data.queue.items.get(row)?.song {
+        SongRef::Queue { id, pos } => Some((id, pos)),
+        _ => None,
+    }
+}
+
+/// The value of `tag` when every song has the same one.
+fn shared<'a>(songs: &'a [Song], tag: &str) -> Option<&'a String> {
+    let first = songs.first()?.tags.get(tag)?;
+    songs
+        .iter()
+        .all(|song| song.tags.get(tag) == Some(first))
+        .then_some(first)
+}
+
+/// Feeds a key typed while the menu is open.
+pub fn input(key: Key, data: &mut Data, client: &mut Client) -> Result<()> {
+    let wrap = data.wrap;
+    let menu = match data.menu.as_mut() {
+        Some(menu) => menu,
+        None => return Ok(()),
+    };
+    match key {
+        Key::Esc | Key::Ctrl('c') | Key::Char('q') | Key::Char('m') => data.menu = None,
+        Key::Char('\n') => {
+            if let Some(menu) = data.menu.take() {
+                let row = menu.list.state.selected().unwrap_or(0);
+                if let Some(&choice) = menu.choices.get(row) {
+                    run(menu, choice, data, client)?;
+                }
+            }
+        }
+        _ => {
+            if let Some(motion) = keys::popup_motion(key) {
+                menu.list.motion(motion, wrap);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run(menu: Menu, choice: Choice, data: &mut Data, client: &mut Client) -> Result<()> {
+    let Menu {
+        songs,
+        batch,
+        queued,
+        ..
+    } = menu;
+    match choice {
+        Choice::PlayNow => {
+            match queued {
+                Some((id, _)) => client.switch(id)?,
+                None => {
+                    let added = append(&songs, batch.as_ref(), client)?;
+                    if added.is_empty() {
+                        return update_queue(data, client);
+                    }
+                    client.switch(added.start)?;
+                }
+            }
+            client.play()?;
+            update_queue(data, client)
+        }
+        Choice::AddNext => {
+            let current = data.current.place.map(|place| place.pos);
+            match (queued, current) {
+                (Some((id, pos)), Some(current)) if pos != current => {
+                    // Taking the song out first moves the current one up.
+                    let to = if pos < current { current } else { current + 1 };
+                    client.shift(id, to as usize)?;
+                }
+                (None, Some(current)) => {
+                    let added = append(&songs, batch.as_ref(), client)?;
+                    if !added.is_empty() {
+                        client.shift(added, current as usize + 1)?;
+                    }
+                }
+                // Nothing is playing, so next is the end of the queue.
+                (None, None) => {
+                    append(&songs, batch.as_ref(), client)?;
+                }
+                _ => {}
+            }
+            update_queue(data, client)
+        }
+        Choice::Append => {
+            append(&songs, batch.as_ref(), client)?;
+            update_queue(data, client)
+        }
+        Choice::AddToPlaylist => playlists::ask_add(data, client, songs),
+        Choice::Info => {
+            if let Some(song) = songs.into_iter().next() {
+                Info::show(data, song);
+            }
+            Ok(())
+        }
+        Choice::GoToArtist => match shared(&songs, "Artist") {
+            Some(artist) => go_to_artist(data, client, artist).map(|_| ()),
+            None => Ok(()),
+        },
+        Choice::GoToAlbum => match (shared(&songs, "Artist"), shared(&songs, "Album")) {
+            (Some(artist), Some(album)) => {
+                if go_to_artist(data, client, artist)? {
+                    let row = data
+                        .library
+                        .items
+                        .iter()
+                        .position(|i| i.tag == LibKind::Album && &i.content == album);
+                    if let Some(row) = row {
+                        data.library.state.select(Some(row));
+                        dispatch::open_album(data, client)?;
+                    }
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        },
+    }
+}
+
+/// Shows the albums of `artist` in the library tab. Returns false when the
+/// library has no such artist.
+fn go_to_artist(data: &mut Data, client: &mut Client, artist: &str) -> Result<bool> {
+    let row = match data.artists.items.iter().position(|i| i.content == artist) {
+        Some(row) => row,
+        None => return Ok(false),
+    };
+    data.tabindex = 1;
+    // Matches of the last search point into the list being replaced.
+    data.search = None;
+    data.library = data.artists.clone();
+    data.library.state.select(Some(row));
+    dispatch::open_artist(data, client)?;
+    Ok(true)
+}
+
+/// Adds `songs` to the end of the queue, in one command when `batch` has
+/// one, and returns the positions they took.
+fn append(songs: &[Song], batch: Option<&Batch>, client: &mut Client) -> Result<Range<u32>> {
+    let start = client.status()?.queue_len;
+    match batch {
+        Some(Batch::Find(terms)) => libs::with_query(terms, |query| client.findadd(query))?,
+        Some(Batch::Playlist(name)) => client.load(name.as_str(), ..)?,
+        None => {
+            for song in songs {
+                client.push(song)?;
+            }
+        }
+    }
+    Ok(start..client.status()?.queue_len)
+}
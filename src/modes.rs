@@ -0,0 +1,217 @@
+use crate::connection::Client;
+use crate::libs::Data;
+
+use mpd::error::Result;
+
+/// Crossfade turned on from the header keys when none was set before.
+const CROSSFADE: i64 = 5;
+/// Steps taken by `increase` and `decrease` on the MixRamp rows.
+const MIXRAMP_DB: f32 = 1.0;
+const MIXRAMP_DELAY: f64 = 0.5;
+
+/// Single mode. `Oneshot` stops after the current song and then turns itself
+/// off.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Single {
+    Off,
+    On,
+    Oneshot,
+}
+
+impl Single {
+    fn next(self) -> Single {
+        match self {
+            Single::Off => Single::On,
+            Single::On => Single::Oneshot,
+            Single::Oneshot => Single::Off,
+        }
+    }
+
+    fn previous(self) -> Single {
+        self.next().next()
+    }
+
+    fn argument(self) -> &'static str {
+        match self {
+            Single::Off => "0",
+            Single::On => "1",
+            Single::Oneshot => "oneshot",
+        }
+    }
+}
+
+/// The modes `Status` gets wrong: the mpd crate reads `single: oneshot` as
+/// off and never reads the MixRamp fields.
+#[derive(Clone, Copy, Debug)]
+pub struct Modes {
+    pub single: Single,
+    pub mixrampdb: f32,
+    /// `None` while MixRamp is off.
+    pub mixrampdelay: Option<f64>,
+}
+
+impl Default for Modes {
+    fn default() -> Modes {
+        Modes {
+            single: Single::Off,
+            mixrampdb: 0.0,
+            mixrampdelay: None,
+        }
+    }
+}
+
+impl Modes {
+    pub fn fetch(client: &mut Client) -> Result<Modes> {
+        let mut modes = Modes::default();
+        for (key, value) in client.command("status")? {
+            match key.as_str() {
+                "single" => {
+                    modes.single = match value.as_str() {
+                        "1" => Single::On,
+                        "oneshot" => Single::Oneshot,
+                        _ => Single::Off,
+                    }
+                }
+                "mixrampdb" => modes.mixrampdb = value.parse()?,
+                "mixrampdelay" => {
+                    modes.mixrampdelay = value.parse().ok().filter(|d: &f64| *d > 0.0)
+                }
+                _ => {}
+            }
+        }
+        Ok(modes)
+    }
+}
+
+/// A row of the options pane.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Row {
+    Repeat,
+    Random,
+    Single,
+    Consume,
+    Crossfade,
+    MixrampDb,
+    MixrampDelay,
+}
+
+pub const ROWS: [Row; 7] = [
+    Row::Repeat,
+    Row::Random,
+    Row::Single,
+    Row::Consume,
+    Row::Crossfade,
+    Row::MixrampDb,
+    Row::MixrampDelay,
+];
+
+impl Row {
+    pub fn label(self) -> &'static str {
+        match self {
+            Row::Repeat => "Repeat",
+            Row::Random => "Random",
+            Row::Single => "Single",
+            Row::Consume => "Consume",
+            Row::Crossfade => "Crossfade",
+            Row::MixrampDb => "MixRamp threshold",
+            Row::MixrampDelay => "MixRamp delay",
+        }
+    }
+
+    pub fn value(self, data: &Data) -> String {
+        let on_off = |on: bool| String::from(if on { "on" } else { "off" });
+        match self {
+            Row::Repeat => on_off(data.status.repeat),
+            Row::Random => on_off(data.status.random),
+            Row::Single => match data.modes.single {
+                Single::Off => on_off(false),
+                Single::On => on_off(true),
+                Single::Oneshot => String::from("oneshot"),
+            },
+            Row::Consume => on_off(data.status.consume),
+            Row::Crossfade => match crossfade(data) {
+                0 => on_off(false),
+                secs => format!("{}s", secs),
+            },
+            Row::MixrampDb => format!("{} dB", data.modes.mixrampdb),
+            Row::MixrampDelay => match data.modes.mixrampdelay {
+                Some(delay) => format!("{}s", delay),
+                None => on_off(false),
+            },
+        }
+    }
+}
+
+fn crossfade(data: &Data) -> i64 {
+    data.status.crossfade.map_or(0, |c| c.num_seconds())
+}
+
+pub fn toggle_repeat(data: &Data, client: &mut Client) -> Result<()> {
+    client.repeat(!data.status.repeat)
+}
+
+pub fn toggle_random(data: &Data, client: &mut Client) -> Result<()> {
+    client.random(!data.status.random)
+}
+
+pub fn toggle_consume(data: &Data, client: &mut Client) -> Result<()> {
+    client.consume(!data.status.consume)
+}
+
+/// Goes from off to on to oneshot and back to off.
+pub fn cycle_single(data: &Data, client: &mut Client) -> Result<()> {
+    set_single(client, data.modes.single.next())
+}
+
+pub fn set_single(client: &mut Client, single: Single) -> Result<()> {
+    let command = format!("single {}", single.argument());
+    client.command(&command).map(|_| ())
+}
+
+/// Turns crossfade off, or on at `CROSSFADE` seconds.
+pub fn toggle_crossfade(data: &Data, client: &mut Client) -> Result<()> {
+    match crossfade(data) {
+        0 => client.crossfade(CROSSFADE),
+        _ => client.crossfade(0),
+    }
+}
+
+/// The row under the cursor in the options pane.
+fn selected(data: &Data) -> Option<Row> {
+    ROWS.get(data.options_state.selected()?).copied()
+}
+
+/// Toggles the row under the cursor.
+pub fn enter(data: &Data, client: &mut Client) -> Result<()> {
+    match selected(data) {
+        Some(Row::Repeat) => toggle_repeat(data, client),
+        Some(Row::Random) => toggle_random(data, client),
+        Some(Row::Single) => cycle_single(data, client),
+        Some(Row::Consume) => toggle_consume(data, client),
+        Some(Row::Crossfade) => toggle_crossfade(data, client),
+        Some(Row::MixrampDb) | Some(Row::MixrampDelay) | None => Ok(()),
+    }
+}
+
+/// Steps the value of the row under the cursor up, or down when `up` is not
+/// set. On/off rows are toggled either way.
+pub fn step(data: &Data, client: &mut Client, up: bool) -> Result<()> {
+    let sign = if up { 1 } else { -1 };
+    match selected(data) {
+        Some(Row::Single) => {
+            let single = data.modes.single;
+            set_single(client, if up { single.next() } else { single.previous() })
+        }
+        Some(Row::Crossfade) => client.crossfade((crossfade(data) + sign).max(0)),
+        Some(Row::MixrampDb) => {
+            // MixRamp thresholds are loudness levels below full scale.
+            client.mixrampdb((data.modes.mixrampdb + sign as f32 * MIXRAMP_DB).min(0.0))
+        }
+        Some(Row::MixrampDelay) => {
+            let delay = data.modes.mixrampdelay.unwrap_or(0.0) + sign as f64 * MIXRAMP_DELAY;
+            // mpd turns MixRamp off when the delay is not a number.
+            client.mixrampdelay(if delay > 0.0 { delay } else { f64::NAN })
+        }
+        _ => enter(data, client),
+    }
+}
@@ -0,0 +1,159 @@
+use crate::connection::Client;
+use crate::dispatch;
+use crate::finder;
+use crate::info;
+use crate::keys::Action;
+use crate::libs::{Data, Library};
+use crate::menu;
+use crate::seek;
+use crate::ui::contains;
+
+use mpd::error::Result;
+use std::time::{Duration, Instant};
+use termion::event::{Key, MouseButton, MouseEvent};
+use tui::layout::Rect;
+
+/// Longest gap between two clicks on a row that still counts as a double click.
+const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// Maps a mouse event onto whatever was drawn under it.
+pub fn input(event: MouseEvent, data: &mut Data, client: &mut Client) -> Result<()> {
+    if data.drag.is_some() {
+        return seek::mouse(event, data, client);
+    }
+    let (button, x, y) = match event {
+        MouseEvent::Press(button, x, y) => (button, x, y),
+        _ => return Ok(()),
+    };
+    if contains(data.areas.position, x, y) {
+        return seek::mouse(event, data, client);
+    }
+
+    if data.finder.as_ref().is_some_and(|finder| finder.open) {
+        let area = data.areas.finder;
+        if !contains(area, x, y) {
+            // Clicking next to the popup closes it.
+            if button == MouseButton::Left {
+                finder::input(Key::Esc, data, client)?;
+            }
+            return Ok(());
+        }
+        let double = match data.finder.as_mut() {
+            Some(finder) => press(&mut finder.results, button, area, y, &mut data.click),
+            None => false,
+        };
+        if double {
+            finder::input(Key::Char('\n'), data, client)?;
+        }
+        return Ok(());
+    }
+
+    if data.menu.is_some() {
+        let area = data.areas.menu;
+        if !contains(area, x, y) {
+            if button == MouseButton::Left {
+                data.menu = None;
+            }
+            return Ok(());
+        }
+        if let Some(menu) = data.menu.as_mut() {
+            press(&mut menu.list, button, area, y, &mut data.click);
+        }
+        // Unlike list rows, a single click picks a choice.
+        if button == MouseButton::Left {
+            menu::input(Key::Char('\n'), data, client)?;
+        }
+        return Ok(());
+    }
+    if data.info.is_some() {
+        let area = data.areas.info;
+        if !contains(area, x, y) {
+            if button == MouseButton::Left {
+                data.info = None;
+            }
+            return Ok(());
+        }
+        let double = match data.info.as_mut() {
+            Some(info) => press(&mut info.rows, button, area, y, &mut data.click),
+            None => false,
+        };
+        if double {
+            info::input(Key::Char('\n'), data);
+        }
+        return Ok(());
+    }
+    if data.help.is_some() {
+        let area = data.areas.help;
+        if !contains(area, x, y) {
+            if button == MouseButton::Left {
+                data.help = None;
+            }
+            return Ok(());
+        }
+        if let Some(help) = data.help.as_mut() {
+            press(&mut help.rows, button, area, y, &mut data.click);
+        }
+        return Ok(());
+    }
+
+    if contains(data.areas.volume, x, y) {
+        match button {
+            MouseButton::WheelUp => return dispatch::run(Action::VolumeUp, data, client),
+            MouseButton::WheelDown => return dispatch::run(Action::VolumeDown, data, client),
+            _ => {}
+        }
+    }
+    if button == MouseButton::Left {
+        if let Some(tab) = data.areas.tabs.iter().position(|&t| contains(t, x, y)) {
+            data.tabindex = tab;
+            return Ok(());
+        }
+    }
+
+    let area = data.areas.list;
+    if !contains(area, x, y) {
+        return Ok(());
+    }
+    let list = match data.tabindex {
+        1 => &mut data.library,
+        2 => &mut data.playlists,
+        3 => &mut data.queue,
+        _ => return Ok(()),
+    };
+    if press(list, button, area, y, &mut data.click) {
+        dispatch::run(Action::Enter, data, client)?;
+    }
+    Ok(())
+}
+
+/// Handles a press at row `y` of `list`, drawn in `area`: the wheel moves the
+/// selection and a click selects the row under the mouse. Returns true for
+/// the second click of a double click, which `click` keeps track of.
+fn press(
+    list: &mut Library,
+    button: MouseButton,
+    area: Rect,
+    y: u16,
+    click: &mut Option<(Instant, usize)>,
+) -> bool {
+    match button {
+        MouseButton::WheelUp => list.previous(),
+        MouseButton::WheelDown => list.next(),
+        MouseButton::Left => {
+            let row = list.state.top() + (y - 1 - area.y) as usize;
+            if row >= list.items.len() {
+                return false;
+            }
+            list.state.select(Some(row));
+            let double = click.is_some_and(|(at, last)| last == row && at.elapsed() < DOUBLE_CLICK);
+            *click = if double {
+                None
+            } else {
+                Some((Instant::now(), row))
+            };
+            return double;
+        }
+        _ => {}
+    }
+    false
+}
@@ -0,0 +1,859 @@
+//! The MPRIS interface on the session bus, so media keys, desktop widgets and
+//! `playerctl` can see and control what mpd is playing.
+
+use crate::connection::Client;
+use crate::dispatch;
+use crate::eve::Event;
+use crate::keys::Action;
+use crate::libs::Data;
+use crate::modes::{self, Single};
+use crate::seek;
+
+use mpd::error::Result;
+use mpd::status;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::mem;
+use std::process;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Instant;
+use termion::event::Key;
+use zbus::blocking::Connection;
+use zbus::fdo::{self, RequestNameFlags};
+use zbus::interface;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{ObjectPath, Value};
+
+pub const NAME: &str = "org.mpris.MediaPlayer2.flux";
+const PATH: &str = "/org/mpris/MediaPlayer2";
+const PLAYER: &str = "org.mpris.MediaPlayer2.Player";
+const PROPERTIES: &str = "org.freedesktop.DBus.Properties";
+
+/// Where track ids live; the rest of the path is the mpd song id.
+const TRACKS: &str = "/org/flux/track/";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// How far, in microseconds, the position may drift from where playing on
+/// would have taken it before clients are told of a seek.
+const SEEKED: i64 = 1_500_000;
+
+/// What a client asked for, carried out by the main loop.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Play,
+    Pause,
+    PlayPause,
+    Stop,
+    Next,
+    Previous,
+    /// Microseconds to move by, backward when negative.
+    Seek(i64),
+    /// The mpd id of the song, and microseconds into it.
+    SetPosition(u32, i64),
+    /// From 0 to 1.
+    Volume(f64),
+    /// `None`, `Track` or `Playlist`.
+    Loop(String),
+    Shuffle(bool),
+}
+
+/// The song playing, as clients see it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Track {
+    /// The mpd song id.
+    pub id: u32,
+    pub title: String,
+    pub artists: Vec<String>,
+    pub album: String,
+    pub album_artists: Vec<String>,
+    pub number: Option<i32>,
+    /// In microseconds.
+    pub length: Option<i64>,
+    /// Streams only; files are relative to the music directory.
+    pub url: Option<String>,
+}
+
+/// The player as clients see it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct State {
+    pub status: &'static str,
+    pub loop_status: &'static str,
+    pub shuffle: bool,
+    /// From 0 to 1.
+    pub volume: f64,
+    pub track: Option<Track>,
+    /// Microseconds into the track.
+    pub position: i64,
+}
+
+impl Default for State {
+    fn default() -> State {
+        State {
+            status: "Stopped",
+            loop_status: "None",
+            shuffle: false,
+            volume: 0.0,
+            track: None,
+            position: 0,
+        }
+    }
+}
+
+impl State {
+    pub fn of(data: &Data) -> State {
+        let status = &data.status;
+        let song = &data.current;
+        let track = song.place.map(|place| {
+            let tag = |name: &str| song.tags.get(name).cloned().unwrap_or_default();
+            let list = |name: &str| song.tags.get(name).cloned().into_iter().collect();
+            Track {
+                id: place.id.0,
+                title: song.title.clone().unwrap_or_else(|| song.file.clone()),
+                artists: list("Artist"),
+                album: tag("Album"),
+                album_artists: list("AlbumArtist"),
+                number: tag("Track").split('/').next().and_then(|n| n.parse().ok()),
+                length: status
+                    .duration
+                    .or(song.duration)
+                    .and_then(|d| d.num_microseconds()),
+                url: Some(song.file.clone()).filter(|file| file.contains("://")),
+            }
+        });
+        State {
+            status: match status.state {
+                status::State::Play => "Playing",
+                status::State::Pause => "Paused",
+                status::State::Stop => "Stopped",
+            },
+            loop_status: match (status.repeat, data.modes.single) {
+                (true, Single::On) => "Track",
+                (true, _) => "Playlist",
+                (false, _) => "None",
+            },
+            shuffle: status.random,
+            volume: status.volume.max(0) as f64 / 100.0,
+            track,
+            position: status
+                .elapsed
+                .and_then(|e| e.num_microseconds())
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// The last state set, and when.
+struct Shared {
+    state: State,
+    at: Instant,
+}
+
+impl Shared {
+    /// Where playback should be now.
+    fn position(&self) -> i64 {
+        match self.state.status {
+            "Playing" => self.state.position + self.at.elapsed().as_micros() as i64,
+            _ => self.state.position,
+        }
+    }
+}
+
+/// The service, as the main loop holds it.
+pub struct Mpris {
+    connection: Connection,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Mpris {
+    pub fn update(&self, data: &Data) {
+        self.set(State::of(data));
+    }
+
+    /// Takes on `state`, announcing what changed.
+    pub fn set(&self, state: State) {
+        let mut shared = lock(&self.shared);
+        let expected = shared.position();
+        let old = mem::replace(&mut shared.state, state);
+        shared.at = Instant::now();
+        let new = &shared.state;
+
+        let changed: HashMap<_, _> = player(new)
+            .into_iter()
+            .zip(player(&old))
+            .filter(|((name, new), (_, old))| *name != "Position" && new != old)
+            .map(|(property, _)| property)
+            .collect();
+        let same_track = match (&new.track, &old.track) {
+            (Some(new), Some(old)) => new.id == old.id,
+            _ => false,
+        };
+        let seeked = same_track && (new.position - expected).abs() > SEEKED;
+        let position = new.position;
+        drop(shared);
+
+        // The bus going away only matters to clients, which went with it.
+        if !changed.is_empty() {
+            let body = (PLAYER, changed, Vec::<&str>::new());
+            let _ = self.connection.emit_signal(
+                None::<&str>,
+                PATH,
+                PROPERTIES,
+                "PropertiesChanged",
+                &body,
+            );
+        }
+        if seeked {
+            let _ = self
+                .connection
+                .emit_signal(None::<&str>, PATH, PLAYER, "Seeked", &position);
+        }
+    }
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    shared
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Offers the interface on the session bus, sending what clients ask for as
+/// `Event::Mpris`. Returns `None` without a session bus.
+pub fn start(tx: Sender<Event<Key>>) -> Option<Mpris> {
+    let connection = Connection::session().ok()?;
+    serve(connection, move |command| {
+        tx.send(Event::Mpris(command)).is_ok()
+    })
+    .ok()
+}
+
+/// Takes a name for the interface on `connection`, whose own thread answers
+/// calls. `commands` is handed what clients ask for, and returns false once
+/// nobody is there to carry it out.
+pub fn serve(
+    connection: Connection,
+    commands: impl Fn(Command) -> bool + Send + Sync + 'static,
+) -> zbus::Result<Mpris> {
+    let shared = Arc::new(Mutex::new(Shared {
+        state: State::default(),
+        at: Instant::now(),
+    }));
+    let player = Player {
+        shared: shared.clone(),
+        commands: Box::new(commands),
+    };
+    // Both are in place before clients can find them by name.
+    connection.object_server().at(PATH, Root)?;
+    connection.object_server().at(PATH, player)?;
+    request_name(&connection)?;
+    Ok(Mpris { connection, shared })
+}
+
+/// Takes the well-known name, or a name of its own when another flux has it.
+fn request_name(connection: &Connection) -> zbus::Result<()> {
+    for name in [
+        NAME.to_string(),
+        format!("{}.instance{}", NAME, process::id()),
+    ] {
+        // Fail rather than wait in line for the name.
+        match connection.request_name_with_flags(name.as_str(), RequestNameFlags::DoNotQueue.into())
+        {
+            Err(zbus::Error::NameTaken) => continue,
+            result => return result.map(drop),
+        }
+    }
+    Err(zbus::Error::NameTaken)
+}
+
+/// `org.mpris.MediaPlayer2`, which says who the player is.
+struct Root;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+    /// There is no window to raise.
+    fn raise(&self) {}
+
+    /// The interface quits with flux, not the other way round.
+    fn quit(&self) {}
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn identity(&self) -> &str {
+        "flux"
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec![]
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn supported_mime_types(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// `org.mpris.MediaPlayer2.Player`, which shows what is playing and hands
+/// what clients ask for to `commands`.
+struct Player {
+    shared: Arc<Mutex<Shared>>,
+    commands: Box<dyn Fn(Command) -> bool + Send + Sync>,
+}
+
+impl Player {
+    fn run(&self, command: Command) -> fdo::Result<()> {
+        if (self.commands)(command) {
+            Ok(())
+        } else {
+            Err(fdo::Error::Failed("flux is exiting".into()))
+        }
+    }
+
+    fn state(&self) -> State {
+        lock(&self.shared).state.clone()
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn next(&self) -> fdo::Result<()> {
+        self.run(Command::Next)
+    }
+
+    fn previous(&self) -> fdo::Result<()> {
+        self.run(Command::Previous)
+    }
+
+    fn pause(&self) -> fdo::Result<()> {
+        self.run(Command::Pause)
+    }
+
+    fn play_pause(&self) -> fdo::Result<()> {
+        self.run(Command::PlayPause)
+    }
+
+    fn stop(&self) -> fdo::Result<()> {
+        self.run(Command::Stop)
+    }
+
+    fn play(&self) -> fdo::Result<()> {
+        self.run(Command::Play)
+    }
+
+    fn seek(&self, offset: i64) -> fdo::Result<()> {
+        self.run(Command::Seek(offset))
+    }
+
+    fn set_position(&self, track_id: ObjectPath<'_>, position: i64) -> fdo::Result<()> {
+        match track_id.strip_prefix(TRACKS).and_then(|id| id.parse().ok()) {
+            Some(id) => self.run(Command::SetPosition(id, position)),
+            // A track that is gone is ignored, as the interface asks.
+            None => Ok(()),
+        }
+    }
+
+    fn open_uri(&self, _uri: &str) -> fdo::Result<()> {
+        Err(fdo::Error::NotSupported("flux does not open URIs".into()))
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> &str {
+        self.state().status
+    }
+
+    #[zbus(property)]
+    fn loop_status(&self) -> &str {
+        self.state().loop_status
+    }
+
+    #[zbus(property)]
+    fn set_loop_status(&self, status: String) -> fdo::Result<()> {
+        if !["None", "Track", "Playlist"].contains(&status.as_str()) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "no loop status {}",
+                status
+            )));
+        }
+        self.run(Command::Loop(status))
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    /// mpd plays at one speed only.
+    #[zbus(property)]
+    fn set_rate(&self, _rate: f64) {}
+
+    #[zbus(property)]
+    fn shuffle(&self) -> bool {
+        self.state().shuffle
+    }
+
+    #[zbus(property)]
+    fn set_shuffle(&self, on: bool) -> fdo::Result<()> {
+        self.run(Command::Shuffle(on))
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<&str, Value<'_>> {
+        metadata(self.state().track.as_ref())
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.state().volume
+    }
+
+    #[zbus(property)]
+    fn set_volume(&self, volume: f64) -> fdo::Result<()> {
+        self.run(Command::Volume(volume))
+    }
+
+    /// Clients ask for the position rather than being told of it.
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        lock(&self.shared).position()
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        self.state().track.is_some()
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        self.state().track.is_some()
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        can_seek(&self.state())
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// The player properties of `state`, with the position as it was set.
+fn player(state: &State) -> Vec<(&'static str, Value<'static>)> {
+    let track = state.track.as_ref();
+    vec![
+        ("PlaybackStatus", Value::from(state.status)),
+        ("LoopStatus", Value::from(state.loop_status)),
+        ("Rate", Value::from(1.0)),
+        ("Shuffle", Value::from(state.shuffle)),
+        ("Metadata", Value::from(metadata(track))),
+        ("Volume", Value::from(state.volume)),
+        ("Position", Value::from(state.position)),
+        ("MinimumRate", Value::from(1.0)),
+        ("MaximumRate", Value::from(1.0)),
+        ("CanGoNext", Value::from(true)),
+        ("CanGoPrevious", Value::from(true)),
+        ("CanPlay", Value::from(track.is_some())),
+        ("CanPause", Value::from(track.is_some())),
+        ("CanSeek", Value::from(can_seek(state))),
+        ("CanControl", Value::from(true)),
+    ]
+}
+
+fn can_seek(state: &State) -> bool {
+    state.track.as_ref().is_some_and(|t| t.length.is_some())
+}
+
+fn metadata(track: Option<&Track>) -> HashMap<&'static str, Value<'static>> {
+    let path = |path: String| Value::from(ObjectPath::try_from(path).unwrap());
+    let track = match track {
+        Some(track) => track,
+        None => return HashMap::from([("mpris:trackid", path(NO_TRACK.into()))]),
+    };
+    let mut entries = HashMap::from([
+        ("mpris:trackid", path(format!("{}{}", TRACKS, track.id))),
+        ("xesam:title", Value::from(track.title.clone())),
+    ]);
+    if let Some(length) = track.length {
+        entries.insert("mpris:length", Value::from(length));
+    }
+    if !track.artists.is_empty() {
+        entries.insert("xesam:artist", Value::from(track.artists.clone()));
+    }
+    if !track.album.is_empty() {
+        entries.insert("xesam:album", Value::from(track.album.clone()));
+    }
+    if !track.album_artists.is_empty() {
+        entries.insert(
+            "xesam:albumArtist",
+            Value::from(track.album_artists.clone()),
+        );
+    }
+    if let Some(number) = track.number {
+        entries.insert("xesam:trackNumber", Value::from(number));
+    }
+    if let Some(url) = &track.url {
+        entries.insert("xesam:url", Value::from(url.clone()));
+    }
+    entries
+}
+
+/// Carries out what a client asked for.
+pub fn apply(command: Command, data: &mut Data, client: &mut Client) -> Result<()> {
+    match command {
+        Command::Play => client.play(),
+        Command::Pause => client.pause(true),
+        Command::PlayPause => dispatch::run(Action::TogglePause, data, client),
+        Command::Stop => client.stop(),
+        Command::Next => client.next(),
+        Command::Previous => client.prev(),
+        Command::Seek(offset) => seek::by(data, client, offset / 1_000_000),
+        Command::SetPosition(id, position) => {
+            // Only the song playing can be seeked in.
+            match data.current.place {
+                Some(place) if place.id.0 == id => {
+                    seek::to_second(data, client, position / 1_000_000)
+                }
+                _ => Ok(()),
+            }
+        }
+        Command::Volume(volume) => client.volume((volume.clamp(0.0, 1.0) * 100.0).round() as i8),
+        Command::Loop(status) => {
+            let (repeat, single) = match status.as_str() {
+                "Track" => (true, Single::On),
+                "Playlist" => (true, Single::Off),
+                _ => (false, Single::Off),
+            };
+            client.repeat(repeat)?;
+            modes::set_single(client, single)
+        }
+        Command::Shuffle(on) => client.random(on),
+    }
+}
+
+/// These run against a `dbus-daemon` of their own, and fail without one on the
+/// `PATH`. `cargo test --no-default-features` leaves them out.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryInto;
+    use std::env;
+    use std::fs;
+    use std::io::{BufRead, BufReader};
+    use std::path::PathBuf;
+    use std::process::{Child, Command as Process, Stdio};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc::{self, Receiver};
+    use std::time::Duration;
+    use zbus::blocking::connection::Builder;
+    use zbus::blocking::fdo::DBusProxy;
+    use zbus::blocking::MessageIterator;
+    use zbus::message::Type;
+    use zbus::zvariant::{DynamicType, OwnedValue};
+    use zbus::Message;
+
+    const ROOT: &str = "org.mpris.MediaPlayer2";
+
+    /// A bus of its own for each test.
+    struct Daemon {
+        child: Child,
+        dir: PathBuf,
+        address: String,
+    }
+
+    impl Daemon {
+        fn start() -> Daemon {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let dir = env::temp_dir().join(format!(
+                "flux-dbus-{}-{}",
+                process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            let config = dir.join("bus.conf");
+            fs::write(
+                &config,
+                format!(
+                    "<busconfig>
+                       <type>session</type>
+                       <listen>unix:path={}</listen>
+                       <auth>EXTERNAL</auth>
+                       <policy context=\"default\">
+                         <allow send_destination=\"*\" eavesdrop=\"true\"/>
+                         <allow eavesdrop=\"true\"/>
+                         <allow own=\"*\"/>
+                       </policy>
+                     </busconfig>",
+                    dir.join("bus").display()
+                ),
+            )
+            .unwrap();
+            let mut child = Process::new("dbus-daemon")
+                .arg(format!("--config-file={}", config.display()))
+                .args(["--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("dbus-daemon is needed for the MPRIS tests");
+            let mut address = String::new();
+            BufReader::new(child.stdout.take().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+            Daemon {
+                child,
+                dir,
+                address: address.trim().to_string(),
+            }
+        }
+
+        fn connect(&self) -> Connection {
+            Builder::address(self.address.as_str())
+                .unwrap()
+                .method_timeout(Duration::from_secs(5))
+                .build()
+                .unwrap()
+        }
+
+        fn service(&self) -> (Mpris, Receiver<Command>) {
+            let (tx, rx) = mpsc::channel();
+            let mpris = serve(self.connect(), move |command| tx.send(command).is_ok()).unwrap();
+            (mpris, rx)
+        }
+    }
+
+    impl Drop for Daemon {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn playing() -> State {
+        State {
+            status: "Playing",
+            volume: 0.8,
+            track: Some(Track {
+                id: 7,
+                title: String::from("Song"),
+                artists: vec![String::from("Artist")],
+                length: Some(180_000_000),
+                ..Track::default()
+            }),
+            position: 10_000_000,
+            ..State::default()
+        }
+    }
+
+    fn call<B>(
+        client: &Connection,
+        interface: &str,
+        member: &str,
+        body: &B,
+    ) -> zbus::Result<Message>
+    where
+        B: serde::Serialize + DynamicType,
+    {
+        client.call_method(Some(NAME), PATH, Some(interface), member, body)
+    }
+
+    fn get(client: &Connection, property: &str) -> OwnedValue {
+        let reply = call(client, PROPERTIES, "Get", &(PLAYER, property)).unwrap();
+        reply.body().deserialize().unwrap()
+    }
+
+    fn owned(client: &Connection, name: &str) -> bool {
+        let proxy = DBusProxy::new(client).unwrap();
+        proxy.name_has_owner(name.try_into().unwrap()).unwrap()
+    }
+
+    fn entry(dict: OwnedValue, key: &str) -> Option<OwnedValue> {
+        HashMap::<String, OwnedValue>::try_from(dict)
+            .unwrap()
+            .remove(key)
+    }
+
+    fn path(path: &str) -> Value<'_> {
+        Value::from(ObjectPath::try_from(path).unwrap())
+    }
+
+    #[test]
+    fn properties() {
+        let daemon = Daemon::start();
+        let (mpris, _rx) = daemon.service();
+        let client = daemon.connect();
+        assert!(owned(&client, NAME));
+        assert_eq!(*get(&client, "PlaybackStatus"), Value::from("Stopped"));
+        assert_eq!(
+            *entry(get(&client, "Metadata"), "mpris:trackid").unwrap(),
+            path(NO_TRACK)
+        );
+
+        mpris.set(playing());
+        assert_eq!(*get(&client, "PlaybackStatus"), Value::from("Playing"));
+        assert_eq!(*get(&client, "Volume"), Value::from(0.8));
+        let title = entry(get(&client, "Metadata"), "xesam:title");
+        assert_eq!(*title.unwrap(), Value::from("Song"));
+        let id = entry(get(&client, "Metadata"), "mpris:trackid");
+        assert_eq!(*id.unwrap(), path(&format!("{}7", TRACKS)));
+        let length = entry(get(&client, "Metadata"), "mpris:length");
+        assert_eq!(*length.unwrap(), Value::from(180_000_000i64));
+        match *get(&client, "Position") {
+            Value::I64(position) => assert!(position >= 10_000_000),
+            ref other => panic!("position is {:?}", other),
+        }
+
+        let all = call(&client, PROPERTIES, "GetAll", &ROOT).unwrap();
+        let all: HashMap<String, OwnedValue> = all.body().deserialize().unwrap();
+        assert_eq!(*all["Identity"], Value::from("flux"));
+        assert!(call(&client, PROPERTIES, "Get", &(PLAYER, "Colour")).is_err());
+
+        let introspection = call(
+            &client,
+            "org.freedesktop.DBus.Introspectable",
+            "Introspect",
+            &(),
+        )
+        .unwrap();
+        let xml: String = introspection.body().deserialize().unwrap();
+        assert!(xml.contains(PLAYER));
+    }
+
+    #[test]
+    fn commands() {
+        let daemon = Daemon::start();
+        let (_mpris, rx) = daemon.service();
+        let client = daemon.connect();
+        let expect = |result: zbus::Result<Message>, command: Command| {
+            result.unwrap();
+            assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), command);
+        };
+        let set = |property: &str, value: Value| {
+            call(&client, PROPERTIES, "Set", &(PLAYER, property, value))
+        };
+        expect(call(&client, PLAYER, "PlayPause", &()), Command::PlayPause);
+        expect(call(&client, PLAYER, "Next", &()), Command::Next);
+        expect(
+            call(&client, PLAYER, "Seek", &-5_000_000i64),
+            Command::Seek(-5_000_000),
+        );
+        expect(
+            call(
+                &client,
+                PLAYER,
+                "SetPosition",
+                &(
+                    ObjectPath::try_from(format!("{}7", TRACKS)).unwrap(),
+                    3_000_000i64,
+                ),
+            ),
+            Command::SetPosition(7, 3_000_000),
+        );
+        expect(set("Volume", Value::from(0.5)), Command::Volume(0.5));
+        expect(set("Shuffle", Value::from(true)), Command::Shuffle(true));
+        expect(
+            set("LoopStatus", Value::from("Track")),
+            Command::Loop("Track".into()),
+        );
+
+        assert!(set("LoopStatus", Value::from("Always")).is_err());
+        assert!(set("Position", Value::from(0i64)).is_err());
+        assert!(call(&client, PLAYER, "Dance", &()).is_err());
+        assert!(call(&client, PLAYER, "Seek", &"far").is_err());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn signals() {
+        let daemon = Daemon::start();
+        let (mpris, _rx) = daemon.service();
+        let client = daemon.connect();
+        let rule = format!("type='signal',path='{}'", PATH);
+        let mut signals = MessageIterator::for_match_rule(rule.as_str(), &client, None).unwrap();
+        let mut next = || {
+            let message = signals.next().unwrap().unwrap();
+            assert_eq!(message.message_type(), Type::Signal);
+            message
+        };
+        let member = |message: &Message| message.header().member().unwrap().to_string();
+        type Changed = (String, HashMap<String, OwnedValue>, Vec<String>);
+
+        mpris.set(playing());
+        let changed = next();
+        assert_eq!(member(&changed), "PropertiesChanged");
+        let (interface, properties, _): Changed = changed.body().deserialize().unwrap();
+        assert_eq!(interface, PLAYER);
+        assert_eq!(*properties["PlaybackStatus"], Value::from("Playing"));
+        assert!(!properties.contains_key("Position"));
+
+        // Only what changed is sent, and playing on is no seek.
+        mpris.set(State {
+            volume: 0.3,
+            position: 10_100_000,
+            ..playing()
+        });
+        let (_, properties, _): Changed = next().body().deserialize().unwrap();
+        assert_eq!(properties.len(), 1);
+        assert_eq!(*properties["Volume"], Value::from(0.3));
+
+        mpris.set(State {
+            volume: 0.3,
+            position: 60_000_000,
+            ..playing()
+        });
+        let seeked = next();
+        assert_eq!(member(&seeked), "Seeked");
+        assert_eq!(seeked.body().deserialize::<i64>().unwrap(), 60_000_000);
+    }
+
+    #[test]
+    fn second_instance() {
+        let daemon = Daemon::start();
+        let (_first, _rx) = daemon.service();
+        let (_second, _rx) = daemon.service();
+        let client = daemon.connect();
+        assert!(owned(&client, NAME));
+        assert!(owned(
+            &client,
+            &format!("{}.instance{}", NAME, process::id())
+        ));
+    }
+}
@@ -0,0 +1,279 @@
+//! Desktop notifications when a new song starts, through the notification
+//! daemon on the session bus or a command of the user's choosing.
+
+use crate::control;
+use crate::image::{self, Image};
+use crate::libs::Data;
+
+use serde::Deserialize;
+#[cfg(feature = "dbus")]
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+#[cfg(feature = "dbus")]
+use zbus::blocking::Connection;
+#[cfg(feature = "dbus")]
+use zbus::zvariant::{SerializeDict, Type};
+
+#[cfg(feature = "dbus")]
+const SERVICE: &str = "org.freedesktop.Notifications";
+#[cfg(feature = "dbus")]
+const PATH: &str = "/org/freedesktop/Notifications";
+
+/// How long a notification waits for the cover art of its song.
+const ART_WAIT: Duration = Duration::from_secs(2);
+
+/// The `[notifications]` table of the config.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationsConfig {
+    pub enabled: bool,
+    /// Program and arguments to run instead of asking the notification
+    /// daemon, which builds without the `dbus` feature cannot do. Fields are filled in as in `flux status --format`, and
+    /// `%cover%` is the path of a PNG of the cover art, or empty without one
+    /// or without `$XDG_RUNTIME_DIR` to put it in.
+    pub command: Option<Vec<String>>,
+    /// Milliseconds a notification stays up. The daemon decides when unset.
+    pub timeout: Option<u32>,
+}
+
+/// A notification for the notifying thread to show.
+enum Job {
+    #[cfg(feature = "dbus")]
+    Bus {
+        summary: String,
+        body: String,
+        cover: Option<Image>,
+    },
+    Command {
+        args: Vec<String>,
+        cover: Option<(PathBuf, Image)>,
+    },
+}
+
+/// Watches for new songs from the main loop.
+pub struct Notifications {
+    command: Option<Vec<String>>,
+    /// `None` while notifications are off.
+    tx: Option<Sender<Job>>,
+    /// The mpd id of the last song notified of, or playing at startup.
+    last: Option<u32>,
+    /// A new song waiting on its cover art, and since when.
+    pending: Option<Instant>,
+}
+
+impl Notifications {
+    pub fn new(config: NotificationsConfig, data: &Data) -> Notifications {
+        let tx = if config.enabled {
+            let (tx, rx) = mpsc::channel();
+            #[cfg(feature = "dbus")]
+            let timeout = config.timeout.map_or(-1, |t| t.min(i32::MAX as u32) as i32);
+            thread::spawn(move || {
+                #[cfg(feature = "dbus")]
+                let mut bus = Bus {
+                    connection: None,
+                    shown: 0,
+                    timeout,
+                };
+                for job in rx {
+                    match job {
+                        Job::Command { args, cover } => run(args, cover),
+                        #[cfg(feature = "dbus")]
+                        Job::Bus {
+                            summary,
+                            body,
+                            cover,
+                        } => bus.notify(summary, body, cover),
+                    }
+                }
+            });
+            Some(tx)
+        } else {
+            None
+        };
+        Notifications {
+            command: config.command,
+            tx,
+            last: id(data),
+            pending: None,
+        }
+    }
+
+    /// Notices a new song, and notifies once its cover art is in or has
+    /// taken too long. Pausing, seeking and reconnecting keep the song id, so
+    /// they do not notify again.
+    pub fn update(&mut self, data: &Data) {
+        let tx = match &self.tx {
+            Some(tx) => tx,
+            None => return,
+        };
+        if let Some(id) = id(data).filter(|id| Some(*id) != self.last) {
+            self.last = Some(id);
+            self.pending = Some(Instant::now());
+        }
+        let since = match self.pending {
+            Some(since) => since,
+            None => return,
+        };
+        if !data.art.searched(&data.current) && since.elapsed() < ART_WAIT {
+            return;
+        }
+        self.pending = None;
+
+        let song = &data.current;
+        let cover = data.art.get(song).cloned();
+        let job = match &self.command {
+            Some(command) => {
+                let path = cover.as_ref().and_then(|_| cover_path());
+                let cover_arg = path.as_ref().map_or(String::new(), |p| {
+                    p.display().to_string().replace('%', "%%")
+                });
+                let args = command
+                    .iter()
+                    .map(|arg| {
+                        let arg = arg.replace("%cover%", &cover_arg);
+                        control::fill(&arg, Some(song), &data.status)
+                    })
+                    .collect();
+                Job::Command {
+                    args,
+                    cover: path.zip(cover),
+                }
+            }
+            #[cfg(feature = "dbus")]
+            None => {
+                let tag = |name: &str| song.tags.get(name).map(|t| escape(t));
+                Job::Bus {
+                    summary: song.title.clone().unwrap_or_else(|| song.file.clone()),
+                    body: [tag("Artist"), tag("Album")]
+                        .iter()
+                        .flatten()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    cover,
+                }
+            }
+            #[cfg(not(feature = "dbus"))]
+            None => return,
+        };
+        let _ = tx.send(job);
+    }
+}
+
+fn id(data: &Data) -> Option<u32> {
+    data.current.place.map(|place| place.id.0)
+}
+
+/// Where the cover art goes for the command to read. Only the runtime
+/// directory is private to the user; a fixed name in a shared one such as
+/// `/tmp` could be a link planted by someone else.
+fn cover_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty())?;
+    Some(PathBuf::from(dir).join("flux-cover.png"))
+}
+
+/// Bodies may hold markup, so text is escaped.
+#[cfg(feature = "dbus")]
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn run(args: Vec<String>, cover: Option<(PathBuf, Image)>) {
+    if let Some((path, image)) = cover {
+        let _ = fs::write(path, image::encode_png(&image));
+    }
+    if let Some((program, args)) = args.split_first() {
+        // Waiting reaps the child.
+        let _ = Command::new(program).args(args).status();
+    }
+}
+
+/// The notification daemon, as the notifying thread talks to it.
+#[cfg(feature = "dbus")]
+struct Bus {
+    connection: Option<Connection>,
+    /// The id of the last notification, which the next takes the place of.
+    shown: u32,
+    timeout: i32,
+}
+
+#[cfg(feature = "dbus")]
+impl Bus {
+    fn notify(&mut self, summary: String, body: String, cover: Option<Image>) {
+        if self.connection.is_none() {
+            self.connection = Connection::session().ok();
+        }
+        let connection = match &self.connection {
+            Some(connection) => connection,
+            None => return,
+        };
+        let hints = Hints {
+            category: "x-flux.song",
+            image_data: cover.as_ref().map(image_data),
+        };
+        let body = (
+            "flux",
+            self.shown,
+            "",
+            summary,
+            body,
+            Vec::<&str>::new(),
+            hints,
+            self.timeout,
+        );
+        match connection.call_method(Some(SERVICE), PATH, Some(SERVICE), "Notify", &body) {
+            Ok(reply) => {
+                if let Ok(id) = reply.body().deserialize() {
+                    self.shown = id;
+                }
+            }
+            // Connect afresh next time, in case the bus went away.
+            Err(_) => self.connection = None,
+        }
+    }
+}
+
+#[cfg(feature = "dbus")]
+#[derive(SerializeDict, Type)]
+#[zvariant(signature = "a{sv}", crate = "zbus::zvariant")]
+struct Hints {
+    category: &'static str,
+    #[zvariant(rename = "image-data")]
+    image_data: Option<ImageData>,
+}
+
+/// The picture as the `image-data` hint takes it.
+#[cfg(feature = "dbus")]
+#[derive(Serialize, Type)]
+#[zvariant(crate = "zbus::zvariant")]
+struct ImageData {
+    width: i32,
+    height: i32,
+    /// Bytes per row.
+    stride: i32,
+    alpha: bool,
+    bits_per_sample: i32,
+    channels: i32,
+    pixels: Vec<u8>,
+}
+
+#[cfg(feature = "dbus")]
+fn image_data(image: &Image) -> ImageData {
+    ImageData {
+        width: image.width as i32,
+        height: image.height as i32,
+        stride: image.width as i32 * 3,
+        alpha: false,
+        bits_per_sample: 8,
+        channels: 3,
+        pixels: image.pixels.iter().flatten().copied().collect(),
+    }
+}
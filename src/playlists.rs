@@ -0,0 +1,243 @@
+use crate::connection::Client;
+use crate::library::{LibItem, LibKind, SongRef};
+use crate::libs::{self, update_queue, Data, Library};
+use crate::prompt::{Prompt, Then};
+use crate::queue::Column;
+
+use mpd::{error::Result, Song};
+use std::process;
+
+/// Lists the stored playlists, or the songs of the open one with a `[Back]`
+/// row first. Keeps the cursor row where possible.
+pub fn reload(data: &mut Data, client: &mut Client) -> Result<()> {
+    let items = match &data.playlist {
+        Some(name) => {
+            let mut items = vec![LibItem::new("  [Back]".into(), LibKind::Back)];
+            for song in client.playlist(name.as_str())? {
+                let title = Column::Title.cell(&song);
+                items.push(LibItem::new(title, LibKind::None).song(SongRef::File(song.file)));
+            }
+            items
+        }
+        None => client
+            .playlists()?
+            .into_iter()
+            .map(|playlist| LibItem::new(playlist.name, LibKind::None))
+            .collect(),
+    };
+    let row = data.playlists.state.selected().unwrap_or(0);
+    data.playlists = Library::newlib(items);
+    let last = data.playlists.items.len().saturating_sub(1);
+    data.playlists.state.select(Some(row.min(last)));
+    Ok(())
+}
+
+/// The playlist under the cursor, or the open one.
+pub fn selected(data: &Data) -> Option<String> {
+    if data.playlist.is_some() {
+        return data.playlist.clone();
+    }
+    let row = data.playlists.state.selected()?;
+    data.playlists.items.get(row).map(|i| i.content.clone())
+}
+
+/// Position in the open playlist of the song under the cursor.
+fn position(data: &Data) -> Option<u32> {
+    let row = data.playlists.state.selected()?;
+    match data.playlists.items.get(row)?.tag {
+        LibKind::Back => None,
+        _ => Some(row as u32 - 1),
+    }
+}
+
+/// The songs behind the row under the cursor: a whole playlist, or one song
+/// of the open one.
+pub fn songs(data: &Data, client: &mut Client) -> Result<Vec<Song>> {
+    if data.playlist.is_none() {
+        return match selected(data) {
+            Some(name) => client.playlist(name.as_str()),
+            None => Ok(vec![]),
+        };
+    }
+    let row = data.playlists.state.selected().unwrap_or(0);
+    match data.playlists.items.get(row).map(|i| &i.song) {
+        Some(SongRef::File(file)) => Ok(vec![libs::song(file)]),
+        _ => Ok(vec![]),
+    }
+}
+
+/// Opens the playlist under the cursor. Inside a playlist, goes back on
+/// `[Back]` and plays the song under the cursor otherwise.
+pub fn enter(data: &mut Data, client: &mut Client) -> Result<()> {
+    if data.playlist.is_none() {
+        data.playlist = selected(data);
+        data.playlists.state.select(Some(0));
+        return reload(data, client);
+    }
+    let row = data.playlists.state.selected().unwrap_or(0);
+    match data.playlists.items.get(row) {
+        Some(item) if item.tag == LibKind::Back => {
+            let name = data.playlist.take();
+            reload(data, client)?;
+            let row = data
+                .playlists
+                .items
+                .iter()
+                .position(|i| Some(&i.content) == name.as_ref());
+            data.playlists.state.select(row.or(Some(0)));
+        }
+        Some(LibItem {
+            song: SongRef::File(file),
+            ..
+        }) => {
+            let id = client.push(libs::song(file))?;
+            update_queue(data, client)?;
+            client.switch(id)?;
+            client.play()?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Appends the row under the cursor to the queue: a whole playlist, or one
+/// song of the open one.
+pub fn add_to_queue(data: &mut Data, client: &mut Client) -> Result<()> {
+    if data.playlist.is_none() {
+        return load(data, client, false);
+    }
+    for song in songs(data, client)? {
+        client.push(song)?;
+    }
+    update_queue(data, client)
+}
+
+/// Adds the selected playlist to the queue, replacing what is there when
+/// `replace` is set.
+pub fn load(data: &mut Data, client: &mut Client, replace: bool) -> Result<()> {
+    let name = match selected(data) {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+    if replace {
+        client.clear()?;
+    }
+    client.load(name.as_str(), ..)?;
+    if replace {
+        client.play()?;
+    }
+    update_queue(data, client)
+}
+
+/// Asks for a name to save the queue under.
+pub fn ask_save(data: &mut Data, client: &mut Client) -> Result<()> {
+    Prompt::open(data, " save queue as: ", String::new(), Then::SaveQueue);
+    Prompt::completions(data, names(client)?);
+    Ok(())
+}
+
+/// Saves the queue as `name`, asking first when that replaces a playlist.
+pub fn save(data: &mut Data, client: &mut Client, name: &str) -> Result<()> {
+    if names(client)?.iter().any(|n| n == name) {
+        let label = format!(" replace playlist {}? [y/N] ", name);
+        Prompt::open(
+            data,
+            label,
+            String::new(),
+            Then::ReplacePlaylist(name.into()),
+        );
+        return Ok(());
+    }
+    client.save(name)?;
+    reload(data, client)
+}
+
+/// Saves the queue over the playlist `name`. The queue is saved under a name
+/// of its own first, so the old playlist stays whole if that fails.
+pub fn replace(data: &mut Data, client: &mut Client, name: &str) -> Result<()> {
+    let saved = format!("{}.flux-{}", name, process::id());
+    client.save(&saved)?;
+    if let Err(err) = client.pl_remove(name) {
+        let _ = client.pl_remove(&saved);
+        return Err(err);
+    }
+    client.pl_rename(&saved, name)?;
+    reload(data, client)
+}
+
+pub fn ask_rename(data: &mut Data) {
+    if let Some(name) = selected(data) {
+        Prompt::open(
+            data,
+            " rename to: ",
+            name.clone(),
+            Then::RenamePlaylist(name),
+        );
+    }
+}
+
+pub fn rename(data: &mut Data, client: &mut Client, from: &str, to: &str) -> Result<()> {
+    client.pl_rename(from, to)?;
+    if data.playlist.as_deref() == Some(from) {
+        data.playlist = Some(to.to_string());
+    }
+    reload(data, client)
+}
+
+/// Deletes the playlist under the cursor, or the song under the cursor when a
+/// playlist is open.
+pub fn delete(data: &mut Data, client: &mut Client) -> Result<()> {
+    match (&data.playlist, position(data)) {
+        (Some(name), Some(pos)) => client.pl_delete(name.as_str(), pos)?,
+        (Some(_), None) => return Ok(()),
+        (None, _) => match selected(data) {
+            Some(name) => client.pl_remove(name.as_str())?,
+            None => return Ok(()),
+        },
+    }
+    reload(data, client)
+}
+
+/// Moves the song under the cursor one row up or down in the open playlist.
+pub fn shift(data: &mut Data, client: &mut Client, up: bool) -> Result<()> {
+    let (name, pos) = match (data.playlist.clone(), position(data)) {
+        (Some(name), Some(pos)) => (name, pos),
+        _ => return Ok(()),
+    };
+    let len = data.playlists.items.len() as u32 - 1;
+    let to = match up {
+        true if pos > 0 => pos - 1,
+        false if pos + 1 < len => pos + 1,
+        _ => return Ok(()),
+    };
+    client.pl_shift(name.as_str(), pos, to)?;
+    data.playlists.state.select(Some(to as usize + 1));
+    reload(data, client)
+}
+
+/// Asks which playlist to add `songs` to.
+pub fn ask_add(data: &mut Data, client: &mut Client, songs: Vec<Song>) -> Result<()> {
+    if songs.is_empty() {
+        return Ok(());
+    }
+    Prompt::open(
+        data,
+        " add to playlist: ",
+        String::new(),
+        Then::AddToPlaylist(songs),
+    );
+    Prompt::completions(data, names(client)?);
+    Ok(())
+}
+
+/// Appends `songs` to the playlist `name`, creating it if needed.
+pub fn add(data: &mut Data, client: &mut Client, name: &str, songs: &[Song]) -> Result<()> {
+    for song in songs {
+        client.pl_push(name, song)?;
+    }
+    reload(data, client)
+}
+
+fn names(client: &mut Client) -> Result<Vec<String>> {
+    Ok(client.playlists()?.into_iter().map(|p| p.name).collect())
+}
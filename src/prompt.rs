@@ -0,0 +1,104 @@
+use crate::connection::Client;
+use crate::libs::Data;
+use crate::playlists;
+
+use mpd::{error::Result, Song};
+use termion::event::Key;
+
+/// What the text typed into a prompt is for.
+pub enum Then {
+    SaveQueue,
+    /// Saving the queue over the playlist of this name, once the answer is
+    /// yes.
+    ReplacePlaylist(String),
+    RenamePlaylist(String),
+    AddToPlaylist(Vec<Song>),
+}
+
+/// A line of text asked for in the status bar. Tab completes the text from
+/// `completions`, cycling through them when pressed again.
+pub struct Prompt {
+    pub label: String,
+    pub text: String,
+    then: Then,
+    completions: Vec<String>,
+    /// What was typed before completing, and the completion shown.
+    completing: Option<(String, usize)>,
+}
+
+impl Prompt {
+    pub fn open(data: &mut Data, label: impl Into<String>, text: String, then: Then) {
+        data.prompt = Some(Prompt {
+            label: label.into(),
+            text,
+            then,
+            completions: vec![],
+            completing: None,
+        });
+    }
+
+    pub fn completions(data: &mut Data, completions: Vec<String>) {
+        if let Some(prompt) = data.prompt.as_mut() {
+            prompt.completions = completions;
+        }
+    }
+
+    fn complete(&mut self) {
+        let (typed, start) = match self.completing.take() {
+            Some((typed, last)) => (typed, last + 1),
+            None => (self.text.clone(), 0),
+        };
+        let len = self.completions.len();
+        let found = (start..start + len)
+            .map(|i| i % len.max(1))
+            .find(|&i| self.completions[i].starts_with(&typed));
+        if let Some(i) = found {
+            self.text = self.completions[i].clone();
+            self.completing = Some((typed, i));
+        } else {
+            self.text = typed;
+        }
+    }
+}
+
+/// Feeds a key typed while a prompt is open.
+pub fn input(key: Key, data: &mut Data, client: &mut Client) -> Result<()> {
+    let prompt = match data.prompt.as_mut() {
+        Some(prompt) => prompt,
+        None => return Ok(()),
+    };
+    match key {
+        Key::Esc | Key::Ctrl('c') => data.prompt = None,
+        Key::Char('\n') => {
+            if let Some(prompt) = data.prompt.take() {
+                let text = prompt.text.trim().to_string();
+                if !text.is_empty() {
+                    submit(prompt.then, text, data, client)?;
+                }
+            }
+        }
+        Key::Char('\t') => prompt.complete(),
+        Key::Backspace => {
+            prompt.completing = None;
+            prompt.text.pop();
+        }
+        Key::Char(c) => {
+            prompt.completing = None;
+            prompt.text.push(c);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn submit(then: Then, text: String, data: &mut Data, client: &mut Client) -> Result<()> {
+    match then {
+        Then::SaveQueue => playlists::save(data, client, &text),
+        Then::ReplacePlaylist(name) => match text.to_lowercase().as_str() {
+            "y" | "yes" => playlists::replace(data, client, &name),
+            _ => Ok(()),
+        },
+        Then::RenamePlaylist(from) => playlists::rename(data, client, &from, &text),
+        Then::AddToPlaylist(songs) => playlists::add(data, client, &text, &songs),
+    }
+}
@@ -0,0 +1,377 @@
+use crate::connection::Client;
+use crate::library::{LibItem, SongRef};
+use crate::libs::{update_queue, Data, Library};
+
+use mpd::{error::Result, Id, Song};
+use serde::Deserialize;
+
+/// A column of the queue tab.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Column {
+    Track,
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Date,
+    Genre,
+    Duration,
+    File,
+}
+
+impl Column {
+    pub fn header(self) -> &'static str {
+        match self {
+            Column::Track => "#",
+            Column::Title => "Title",
+            Column::Artist => "Artist",
+            Column::Album => "Album",
+            Column::AlbumArtist => "Album Artist",
+            Column::Date => "Date",
+            Column::Genre => "Genre",
+            Column::Duration => "Time",
+            Column::File => "File",
+        }
+    }
+
+    /// The text shown for `song` in this column.
+    pub fn cell(self, song: &Song) -> String {
+        let tag = |name: &str| song.tags.get(name).cloned().unwrap_or_default();
+        match self {
+            Column::Track => tag("Track").split('/').next().unwrap_or("").to_string(),
+            Column::Title => match &song.title {
+                Some(title) if !title.is_empty() => title.clone(),
+                // Untagged files would otherwise show up as empty rows.
+                _ => song.file.rsplit('/').next().unwrap_or("").to_string(),
+            },
+            Column::Artist => tag("Artist"),
+            Column::Album => tag("Album"),
+            Column::AlbumArtist => tag("AlbumArtist"),
+            Column::Date => tag("Date"),
+            Column::Genre => tag("Genre"),
+            Column::Duration => match song.duration {
+                Some(time) => format!("{:0>2}:{:0>2}", time.num_minutes(), time.num_seconds() % 60),
+                None => String::new(),
+            },
+            Column::File => song.file.clone(),
+        }
+    }
+
+    /// Share of the leftover width a column gets, or `None` for columns that
+    /// are always as wide as their longest cell.
+    fn weight(self) -> Option<u16> {
+        match self {
+            Column::Track | Column::Date | Column::Duration => None,
+            Column::Title | Column::File => Some(3),
+            Column::Artist | Column::Album | Column::AlbumArtist => Some(2),
+            Column::Genre => Some(1),
+        }
+    }
+}
+
+/// The `[queue]` table of the config.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QueueConfig {
+    pub columns: Vec<Column>,
+}
+
+impl Default for QueueConfig {
+    fn default() -> QueueConfig {
+        QueueConfig {
+            columns: vec![
+                Column::Track,
+                Column::Title,
+                Column::Artist,
+                Column::Album,
+                Column::Duration,
+            ],
+        }
+    }
+}
+
+/// Space left between two columns.
+pub const GAP: u16 = 1;
+
+/// Splits `width` between `columns`. Fixed columns get the width of their
+/// longest cell, the others share what is left by weight without growing past
+/// their longest cell.
+pub fn widths(columns: &[Column], items: &[LibItem], width: u16) -> Vec<u16> {
+    let longest: Vec<u16> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            items
+                .iter()
+                .filter_map(|item| item.cells.get(i))
+                .map(|cell| cell.chars().count())
+                .chain(Some(column.header().chars().count()))
+                .max()
+                .unwrap_or(0) as u16
+        })
+        .collect();
+
+    let gaps = GAP * columns.len().saturating_sub(1) as u16;
+    let mut left = width.saturating_sub(gaps);
+    let mut widths = vec![0; columns.len()];
+    for (i, column) in columns.iter().enumerate() {
+        if column.weight().is_none() {
+            widths[i] = longest[i].min(left);
+            left -= widths[i];
+        }
+    }
+
+    let mut flexible: Vec<usize> = (0..columns.len())
+        .filter(|&i| columns[i].weight().is_some())
+        .collect();
+    while !flexible.is_empty() {
+        let total: u16 = flexible.iter().filter_map(|&i| columns[i].weight()).sum();
+        let share = |i: usize| {
+            (left as u32 * columns[i].weight().unwrap_or(0) as u32 / total as u32) as u16
+        };
+        // Columns narrower than their share give the rest back to the others.
+        let (narrow, wide): (Vec<usize>, Vec<usize>) =
+            flexible.iter().partition(|&&i| longest[i] <= share(i));
+        if narrow.is_empty() {
+            for &i in &wide {
+                widths[i] = share(i);
+            }
+            // Rounding leftovers go to the first flexible column.
+            let used: u16 = wide.iter().map(|&i| widths[i]).sum();
+            widths[wide[0]] += left - used;
+            break;
+        }
+        for &i in &narrow {
+            widths[i] = longest[i];
+            left -= longest[i];
+        }
+        flexible = wide;
+    }
+    widths
+}
+
+/// The cells of the queue row for `song`, one per column.
+pub fn cells(columns: &[Column], song: &Song) -> Vec<String> {
+    columns.iter().map(|column| column.cell(song)).collect()
+}
+
+/// Songs picked in the queue tab. Kept by id so the selection follows the
+/// songs when the queue is refreshed or reordered.
+#[derive(Default)]
+pub struct Selection {
+    /// Song the cursor was on when visual mode started.
+    pub anchor: Option<Id>,
+    pub marked: Vec<Id>,
+}
+
+impl Selection {
+    pub fn is_empty(&self) -> bool {
+        self.anchor.is_none() && self.marked.is_empty()
+    }
+
+    /// Forgets songs that are no longer in the queue.
+    pub fn retain(&mut self, items: &[LibItem]) {
+        let queued: Vec<Id> = items.iter().filter_map(|item| item.song.id()).collect();
+        self.marked.retain(|id| queued.contains(id));
+        if self.anchor.is_some_and(|anchor| !queued.contains(&anchor)) {
+            self.anchor = None;
+        }
+    }
+
+    /// Rows covered by the marks and the visual range, in queue order.
+    pub fn rows(&self, queue: &Library) -> Vec<usize> {
+        let cursor = queue.state.selected().unwrap_or(0);
+        let anchor = self.anchor.and_then(|anchor| {
+            queue
+                .items
+                .iter()
+                .position(|item| item.song.id() == Some(anchor))
+        });
+        (0..queue.items.len())
+            .filter(|&row| {
+                let in_range = anchor.is_some_and(|a| a.min(cursor) <= row && row <= a.max(cursor));
+                in_range
+                    || queue.items[row]
+                        .song
+                        .id()
+                        .is_some_and(|id| self.marked.contains(&id))
+            })
+            .collect()
+    }
+}
+
+/// The songs an edit applies to: the selection, or the row under the cursor
+/// when nothing is selected. Sorted by position.
+fn targets(data: &Data) -> Vec<(Id, u32)> {
+    let rows = if data.selection.is_empty() {
+        data.queue.state.selected().into_iter().collect()
+    } else {
+        data.selection.rows(&data.queue)
+    };
+    rows.into_iter()
+        .filter_map(|row| match data.queue.items.get(row)?.song {
+            SongRef::Queue { id, pos } => Some((id, pos)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Starts visual mode at the cursor, or leaves it keeping the rows it covered
+/// marked.
+pub fn visual(data: &mut Data) {
+    if data.selection.anchor.is_some() {
+        for row in data.selection.rows(&data.queue) {
+            if let Some(id) = data.queue.items[row].song.id() {
+                if !data.selection.marked.contains(&id) {
+                    data.selection.marked.push(id);
+                }
+            }
+        }
+        data.selection.anchor = None;
+        return;
+    }
+    let cursor = data.queue.state.selected();
+    data.selection.anchor = cursor
+        .and_then(|row| data.queue.items.get(row))
+        .and_then(|item| item.song.id());
+}
+
+/// Marks or unmarks the song under the cursor and moves on to the next one.
+pub fn mark(data: &mut Data) {
+    let cursor = data.queue.state.selected();
+    let item = cursor.and_then(|row| data.queue.items.get(row));
+    if let Some(id) = item.and_then(|item| item.song.id()) {
+        let marked = &mut data.selection.marked;
+        match marked.iter().position(|&m| m == id) {
+            Some(i) => {
+                marked.remove(i);
+            }
+            None => marked.push(id),
+        }
+    }
+    data.queue.next();
+}
+
+pub fn delete(data: &mut Data, client: &mut Client) -> Result<()> {
+    for (id, _) in targets(data) {
+        client.delete(id)?;
+    }
+    data.selection = Selection::default();
+    update_queue(data, client)
+}
+
+/// Moves the targeted songs one row up or down, keeping their order. Nothing
+/// moves once one of them reaches the end of the queue.
+pub fn shift(data: &mut Data, client: &mut Client, up: bool) -> Result<()> {
+    let mut targets = targets(data);
+    let len = data.queue.items.len() as u32;
+    match (targets.first(), targets.last()) {
+        (Some(&(_, first)), _) if up && first == 0 => return Ok(()),
+        (_, Some(&(_, last))) if !up && last + 1 >= len => return Ok(()),
+        (None, _) => return Ok(()),
+        _ => {}
+    }
+    if !up {
+        targets.reverse();
+    }
+    for (id, pos) in targets {
+        let to = if up { pos - 1 } else { pos + 1 };
+        client.shift(id, to as usize)?;
+    }
+    update_queue(data, client)
+}
+
+pub fn clear(data: &mut Data, client: &mut Client) -> Result<()> {
+    client.clear()?;
+    data.selection = Selection::default();
+    update_queue(data, client)
+}
+
+/// Removes everything but the current song.
+pub fn crop(data: &mut Data, client: &mut Client) -> Result<()> {
+    let pos = match data.current.place {
+        Some(place) => place.pos,
+        None => return Ok(()),
+    };
+    let len = data.queue.items.len() as u32;
+    if pos + 1 < len {
+        client.delete(pos + 1..len)?;
+    }
+    if pos > 0 {
+        client.delete(0..pos)?;
+    }
+    data.selection = Selection::default();
+    update_queue(data, client)
+}
+
+/// Shuffles the rows from the first to the last selected song, or the whole
+/// queue when fewer than two songs are selected.
+pub fn shuffle(data: &mut Data, client: &mut Client) -> Result<()> {
+    let targets = if data.selection.is_empty() {
+        vec![]
+    } else {
+        targets(data)
+    };
+    match (targets.first(), targets.last()) {
+        (Some(&(_, first)), Some(&(_, last))) if first < last => client.shuffle(first..last + 1)?,
+        _ => client.shuffle(..)?,
+    }
+    update_queue(data, client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::LibKind;
+
+    fn row(cells: &[&str]) -> LibItem {
+        let mut item = LibItem::new(String::new(), LibKind::Title);
+        item.cells = cells.iter().map(|cell| cell.to_string()).collect();
+        item
+    }
+
+    const COLUMNS: [Column; 4] = [
+        Column::Track,
+        Column::Title,
+        Column::Artist,
+        Column::Duration,
+    ];
+
+    #[test]
+    fn zero_width() {
+        let items = [row(&["1", "A Song", "A Band", "03:00"])];
+        assert_eq!(widths(&COLUMNS, &items, 0), [0, 0, 0, 0]);
+        assert_eq!(widths(&COLUMNS, &[], 0), [0, 0, 0, 0]);
+        assert_eq!(widths(&[], &items, 10), []);
+    }
+
+    #[test]
+    fn more_columns_than_space() {
+        let items = [row(&["1", "A Song", "A Band", "03:00"])];
+        // Fixed columns come first, in order, and the rest get nothing.
+        assert_eq!(widths(&COLUMNS, &items, 6), [1, 0, 0, 2]);
+        // Not even the gaps fit.
+        assert_eq!(widths(&COLUMNS, &items, 2), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rounding_leftovers() {
+        let long = "x".repeat(50);
+        let columns = [Column::Title, Column::Artist, Column::Genre];
+        let items = [row(&[&long, &long, &long])];
+        // 10 split 3:2:1 rounds down to 5, 3 and 1; the title gets the last one.
+        assert_eq!(widths(&columns, &items, 12), [6, 3, 1]);
+        assert_eq!(widths(&columns, &items, 14), [6, 4, 2]);
+    }
+
+    #[test]
+    fn narrow_columns_give_back() {
+        let title = "x".repeat(40);
+        let columns = [Column::Title, Column::Artist];
+        let items = [row(&[&title, "Band"])];
+        // The artist needs no more than its header.
+        assert_eq!(widths(&columns, &items, 41), [34, 6]);
+        assert_eq!(widths(&columns, &items, 100), [40, 6]);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::connection::Client;
+use crate::library::{LibItem, LibKind, SongRef};
+use crate::libs::{Data, Library};
+use crate::worker::{Check, Request};
+
+use mpd::{error::Result, Query, Song, Term};
+use termion::event::Key;
+
+/// State of the `/` prompt in the Library tab.
+///
+/// While typing, the library only shows the entries matching the query. Enter
+/// brings the full list back with the first match selected, after which `n`
+/// and `N` jump between matches. Tab switches the prompt to a database search
+/// whose results replace the library list.
+pub struct Search {
+    pub query: String,
+    pub server: bool,
+    pub editing: bool,
+    pub source: Library,
+}
+
+impl Search {
+    pub fn open(data: &mut Data) {
+        data.tabindex = 1;
+        data.search = Some(Search {
+            query: String::new(),
+            server: false,
+            editing: true,
+            source: data.library.clone(),
+        });
+    }
+
+    /// Lists the songs whose `tag` is exactly `value`, as if searched for.
+    pub fn tag(data: &mut Data, tag: String, value: String) {
+        let source = match data.search.take() {
+            Some(search) => search.source,
+            None => data.library.clone(),
+        };
+        data.tabindex = 1;
+        data.search = Some(Search {
+            query: value.clone(),
+            server: true,
+            editing: false,
+            source,
+        });
+        data.requests.push(Request::Tag(tag, value));
+    }
+
+    fn matches(&self, item: &LibItem) -> bool {
+        item.tag != LibKind::Back
+            && item
+                .content
+                .to_lowercase()
+                .contains(&self.query.to_lowercase())
+    }
+
+    pub fn filter(&self) -> Library {
+        if self.server {
+            return self.source.clone();
+        }
+        Library::newlib(
+            self.source
+                .items
+                .iter()
+                .filter(|i| self.matches(i))
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// Feeds a key typed while the prompt is open.
+pub fn input(key: Key, data: &mut Data) {
+    let search = match data.search.as_mut() {
+        Some(search) => search,
+        None => return,
+    };
+    match key {
+        Key::Esc => {
+            data.library = search.source.clone();
+            data.search = None;
+            return;
+        }
+        Key::Char('\n') => return submit(data),
+        Key::Char('\t') => search.server = !search.server,
+        Key::Backspace => {
+            search.query.pop();
+        }
+        Key::Char(c) => search.query.push(c),
+        _ => return,
+    }
+    data.library = search.filter();
+}
+
+fn submit(data: &mut Data) {
+    let search = match data.search.as_mut() {
+        Some(search) => search,
+        None => return,
+    };
+    if search.query.is_empty() {
+        data.library = search.source.clone();
+        data.search = None;
+        return;
+    }
+    search.editing = false;
+    data.library = search.source.clone();
+    if !search.server && search.source.items.iter().any(|i| search.matches(i)) {
+        data.library.state.select(Some(0));
+        jump(data, true);
+        return;
+    }
+    search.server = true;
+    data.requests.push(Request::Search(search.query.clone()));
+}
+
+/// Shows the results of a database search, unless the search was closed
+/// before they arrived.
+pub fn results(data: &mut Data, items: Vec<LibItem>) {
+    if !data.search.as_ref().is_some_and(|s| s.server) {
+        return;
+    }
+    data.library = Library::newlib(items);
+    data.library.state.select(Some(0));
+    jump(data, true);
+}
+
+/// Moves the selection to the next (or previous) entry matching the last
+/// query, wrapping around the list.
+pub fn jump(data: &mut Data, forward: bool) {
+    let search = match data.search.as_ref() {
+        Some(search) if !search.query.is_empty() => search,
+        _ => return,
+    };
+    let items = &data.library.items;
+    let len = items.len();
+    let start = data.library.state.selected().unwrap_or(0);
+    let found = (1..=len)
+        .map(|i| {
+            if forward {
+                (start + i) % len
+            } else {
+                (start + len - i % len) % len
+            }
+        })
+        .find(|&i| search.matches(&items[i]));
+    if let Some(i) = found {
+        data.library.state.select(Some(i));
+    }
+}
+
+/// Searches the database for songs whose artist, album or title contain
+/// `query`.
+pub fn find(client: &mut Client, query: &str, check: Check) -> Result<Vec<LibItem>> {
+    Ok(tree(matching(client, query, check)?))
+}
+
+/// The songs whose artist, album or title contain `query`, in library order.
+pub fn matching(client: &mut Client, query: &str, check: Check) -> Result<Vec<Song>> {
+    let mut songs: Vec<Song> = vec![];
+    for tag in &["Artist", "Album", "Title"] {
+        check()?;
+        let mut q = Query::new();
+        let q = q.and(Term::Tag((*tag).into()), query);
+        for song in client.search(q, None)? {
+            if !songs.iter().any(|s| s.file == song.file) {
+                songs.push(song);
+            }
+        }
+    }
+    sort(&mut songs);
+    Ok(songs)
+}
+
+/// Finds the songs whose `tag` is exactly `value`.
+pub fn find_tag(client: &mut Client, tag: &str, value: &str) -> Result<Vec<LibItem>> {
+    let mut q = Query::new();
+    let q = q.and(Term::Tag(tag.to_string().into()), value);
+    Ok(tree(client.find(q, None)?))
+}
+
+/// Sorts by artist, album and track.
+fn sort(songs: &mut [Song]) {
+    let tag = |song: &Song, name: &str| song.tags.get(name).cloned().unwrap_or_default();
+    songs.sort_by_key(|song| {
+        (
+            tag(song, "Artist"),
+            tag(song, "Album"),
+            tag(song, "Track")
+                .split('/')
+                .next()
+                .and_then(|t| t.parse::<u32>().ok()),
+        )
+    });
+}
+
+/// Lays `songs` out as an artist/album/title tree.
+fn tree(mut songs: Vec<Song>) -> Vec<LibItem> {
+    let tag = |song: &Song, name: &str| song.tags.get(name).cloned().unwrap_or_default();
+    sort(&mut songs);
+
+    let mut items = vec![LibItem::new("  [Back]".into(), LibKind::Back)];
+    let (mut artist, mut album) = (None, None);
+    for song in songs {
+        let song_artist = tag(&song, "Artist");
+        let song_album = tag(&song, "Album");
+        if artist.as_ref() != Some(&song_artist) {
+            items.push(LibItem::new(song_artist.clone(), LibKind::Artist));
+            artist = Some(song_artist);
+            album = None;
+        }
+        if album.as_ref() != Some(&song_album) {
+            items.push(LibItem::new(song_album.clone(), LibKind::Album));
+            album = Some(song_album);
+        }
+        let title = song.title.clone().unwrap_or_else(|| song.file.clone());
+        items.push(LibItem::new(title, LibKind::Title).song(SongRef::File(song.file)));
+    }
+    items
+}
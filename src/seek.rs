@@ -0,0 +1,58 @@
+use crate::connection::Client;
+use crate::libs::Data;
+use crate::ui::contains;
+
+use mpd::error::Result;
+use termion::event::{MouseButton, MouseEvent};
+
+/// Seconds jumped by the short and long seek keys.
+pub const SHORT: i64 = 5;
+pub const LONG: i64 = 30;
+
+/// Seeks `seconds` forward, or backward when negative, in the current song.
+pub fn by(data: &mut Data, client: &mut Client, seconds: i64) -> Result<()> {
+    let elapsed = data.status.elapsed.map_or(0, |e| e.num_seconds());
+    to_second(data, client, elapsed + seconds)
+}
+
+/// Seeks to `percent` of the current song.
+pub fn to(data: &mut Data, client: &mut Client, percent: u8) -> Result<()> {
+    let duration = data.status.duration.map_or(0, |d| d.num_seconds());
+    to_second(data, client, duration * percent as i64 / 100)
+}
+
+pub fn to_second(data: &mut Data, client: &mut Client, second: i64) -> Result<()> {
+    let duration = match data.status.duration {
+        Some(duration) if data.current.place.is_some() => duration.num_seconds(),
+        _ => return Ok(()),
+    };
+    let second = second.clamp(0, duration);
+    client.rewind(second)?;
+    // Show the new position right away instead of after the player event.
+    data.status.elapsed = Some(time::Duration::seconds(second));
+    Ok(())
+}
+
+/// Clicks and drags on the progress bar. While dragging only the preview
+/// moves; the seek happens when the button is released.
+pub fn mouse(event: MouseEvent, data: &mut Data, client: &mut Client) -> Result<()> {
+    let area = data.areas.position;
+    let ratio = |x: u16| {
+        // Mouse columns count from 1.
+        let column = x.saturating_sub(1).saturating_sub(area.x);
+        (column as f64 / area.width.max(1) as f64).min(1.0)
+    };
+    match event {
+        MouseEvent::Press(MouseButton::Left, x, y) if contains(area, x, y) => {
+            data.drag = Some(ratio(x));
+        }
+        MouseEvent::Hold(x, _) if data.drag.is_some() => data.drag = Some(ratio(x)),
+        MouseEvent::Release(x, _) if data.drag.is_some() => {
+            data.drag = None;
+            let duration = data.status.duration.map_or(0, |d| d.num_seconds());
+            to_second(data, client, (duration as f64 * ratio(x)) as i64)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
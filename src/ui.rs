@@ -1,18 +1,49 @@
+use crate::art::Cover;
 use crate::library::Tree;
 pub use crate::libs::Data;
+use crate::modes::{self, Single};
 use crate::position::PositionWidget;
+use crate::queue::{self, GAP};
 
 use mpd::status::{State, Status};
 
 use tui::{
     backend::Backend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::Style,
+    style::{Modifier, Style},
     text::{Span, Spans, Text},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Tabs},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Tabs},
     Frame,
 };
 
+/// Where things were drawn last, for mouse input to find them.
+#[derive(Default)]
+pub struct Areas {
+    /// Each title in the tab bar, with the padding around it.
+    pub tabs: Vec<Rect>,
+    pub volume: Rect,
+    /// Rows of the list in the current tab.
+    pub list: Rect,
+    /// Rows of the finder popup.
+    pub finder: Rect,
+    /// Rows of the context menu.
+    pub menu: Rect,
+    /// Rows of the info popup.
+    pub info: Rect,
+    /// Rows of the help popup.
+    pub help: Rect,
+    pub position: Rect,
+    /// Cells the cover art takes, when nothing is open over them.
+    pub cover: Option<Rect>,
+}
+
+/// Whether the mouse position `x`, `y`, which counts from 1, falls inside
+/// `area`.
+pub fn contains(area: Rect, x: u16, y: u16) -> bool {
+    let (x, y) = (x.saturating_sub(1), y.saturating_sub(1));
+    x >= area.x && x < area.right() && y >= area.y && y < area.bottom()
+}
+
 pub fn draw<B: Backend>(f: &mut Frame<B>, data: &mut Data) {
     let block = Block::default().borders(Borders::NONE).style(data.style);
     let chunks = Layout::default()
@@ -30,15 +61,33 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, data: &mut Data) {
 
     f.render_widget(block.clone(), chunks[0]);
     let top = Layout::default()
-        .constraints([Constraint::Min(0), Constraint::Length(4)].as_ref())
+        .constraints(
+            [
+                Constraint::Min(0),
+                Constraint::Length(6),
+                Constraint::Length(4),
+            ]
+            .as_ref(),
+        )
         .direction(Direction::Horizontal)
         .horizontal_margin(1)
         .split(chunks[0]);
-    let tabtitles = [status_icon(&data.status), "  ", " ﳂ ", "  ", "  "]
+    let tabtitles: Vec<Spans> = [status_icon(&data.status), "  ", " ﳂ ", "  ", "  "]
         .iter()
         .cloned()
         .map(|t| Spans::from(Span::styled(t, data.style)))
         .collect();
+    // Same layout as `Tabs`: a space on each side of a title, then the divider.
+    let mut x = top[0].x;
+    data.areas.tabs = tabtitles
+        .iter()
+        .map(|title| {
+            let width = title.width() as u16 + 2;
+            let area = Rect::new(x, top[0].y, width, 1);
+            x += width + 1;
+            area
+        })
+        .collect();
     let tabs = Tabs::new(tabtitles)
         .block(Block::default().borders(Borders::BOTTOM))
         .select(data.tabindex)
@@ -47,10 +96,23 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, data: &mut Data) {
         .divider("|");
     f.render_widget(tabs, top[0]);
 
-    f.render_widget(block.clone(), top[1]);
-    draw_volume(f, data, top[1]);
+    let indicators = Paragraph::new(Spans::from(modes(data)))
+        .block(Block::default().style(data.style).borders(Borders::BOTTOM))
+        .alignment(Alignment::Right);
+    f.render_widget(indicators, top[1]);
+    f.render_widget(block.clone(), top[2]);
+    draw_volume(f, data, top[2]);
+    data.areas.volume = top[2];
+    data.areas.list = Rect::default();
+    data.areas.finder = Rect::default();
+    data.areas.menu = Rect::default();
+    data.areas.info = Rect::default();
+    data.areas.help = Rect::default();
+    data.areas.cover = None;
 
-    if data.options {
+    if data.offline.is_some() {
+        draw_offline(f, data, chunks[1])
+    } else if data.options {
         draw_options(f, data, chunks[1])
     } else {
         match data.tabindex {
@@ -65,9 +127,119 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, data: &mut Data) {
     }
 
     f.render_widget(block, chunks[2]);
-    draw_position(f, &data, chunks[2]);
+    draw_position(f, data, chunks[2]);
 
     draw_status_bar(f, &data, chunks[3]);
+
+    if data.finder.as_ref().is_some_and(|finder| finder.open) {
+        draw_finder(f, data, chunks[1]);
+    }
+    if data.menu.is_some() {
+        draw_menu(f, data, chunks[1]);
+    }
+    if data.info.is_some() {
+        draw_info(f, data, chunks[1]);
+    }
+    if data.help.is_some() {
+        draw_help(f, data, chunks[1]);
+    }
+    // Pictures drawn by the terminal would cover the popups.
+    if data.finder.as_ref().is_some_and(|finder| finder.open)
+        || data.menu.is_some()
+        || data.info.is_some()
+        || data.help.is_some()
+    {
+        data.areas.cover = None;
+    }
+}
+
+/// A `width` by `height` rect in the middle of `area`, shrunk to fit.
+fn centered(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+fn draw_menu<B>(f: &mut Frame<B>, data: &mut Data, area: Rect)
+where
+    B: Backend,
+{
+    let menu = match data.menu.as_mut() {
+        Some(menu) => menu,
+        None => return,
+    };
+    let area = centered(24, menu.height(), area);
+    let block = Block::default().style(data.style).borders(Borders::ALL);
+    data.areas.menu = block.inner(area);
+    let list = Tree::new(&menu.list.items)
+        .block(block)
+        .highlight_style(Style::default().fg(data.colors.highlight));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut menu.list.state);
+}
+
+fn draw_info<B>(f: &mut Frame<B>, data: &mut Data, area: Rect)
+where
+    B: Backend,
+{
+    let info = match data.info.as_mut() {
+        Some(info) => info,
+        None => return,
+    };
+    let label = Style::default().fg(data.colors.highlight);
+    let title = if info.copied {
+        " info · copied "
+    } else {
+        " info "
+    };
+    let height = info.rows.items.len() as u16 + 2;
+    let area = centered(area.width.saturating_sub(8), height, area);
+    let block = Block::default()
+        .title(Span::styled(title, label))
+        .style(data.style)
+        .borders(Borders::ALL);
+    let inner = block.inner(area);
+    data.areas.info = inner;
+    let widths = vec![info.width, inner.width.saturating_sub(info.width + GAP)];
+    let list = Tree::new(&info.rows.items)
+        .block(block)
+        .highlight_style(label)
+        .columns(widths);
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut info.rows.state);
+}
+
+fn draw_help<B>(f: &mut Frame<B>, data: &mut Data, area: Rect)
+where
+    B: Backend,
+{
+    let help = match data.help.as_mut() {
+        Some(help) => help,
+        None => return,
+    };
+    let label = Style::default().fg(data.colors.highlight);
+    let title = format!(" help · {} ", help.scope);
+    let height = help.rows.items.len() as u16 + 2;
+    let area = centered(64, height, area);
+    let block = Block::default()
+        .title(Span::styled(title, label))
+        .style(data.style)
+        .borders(Borders::ALL);
+    let inner = block.inner(area);
+    data.areas.help = inner;
+    let keys = help.width.min(inner.width / 2);
+    let widths = vec![keys, inner.width.saturating_sub(keys + GAP)];
+    let list = Tree::new(&help.rows.items)
+        .block(block)
+        .highlight_style(label)
+        .columns(widths);
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut help.rows.state);
 }
 
 fn draw_volume<B>(f: &mut Frame<B>, data: &mut Data, area: Rect)
@@ -87,7 +259,7 @@ where
     B: Backend,
 {
     let default_artist = String::from("Unknown Artist");
-    let default_album = String::from("Uknown Album");
+    let default_album = String::from("Unknown Album");
     let artist_text = data.current.tags.get("Artist").unwrap_or(&default_artist);
     let album_text = data.current.tags.get("Album").unwrap_or(&default_album);
     let text = vec![
@@ -101,7 +273,116 @@ where
             Style::default().fg(data.colors.highlight),
         )),
         Spans::from(vec![Span::from("  "), Span::from(album_text.clone())]),
-        Spans::from(repeat_shuffle(&data.status)),
+        Spans::from(modes(data)),
+    ];
+    let paragraph = Paragraph::new(text)
+        .block(Block::default().style(data.style))
+        .alignment(Alignment::Left);
+    f.render_widget(Block::default().style(data.style), area);
+    // Cover art goes on the left, as tall as the tab and square, but no more
+    // than half the width. Cells are about twice as tall as wide.
+    let area = match data.art.get(&data.current) {
+        Some(image) => {
+            let width = (area.height * 2).min(area.width / 2);
+            let chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Length(width),
+                    Constraint::Length(1),
+                    Constraint::Min(0),
+                ])
+                .split(area);
+            if !data.art.pixels {
+                f.render_widget(Cover::new(image), chunks[0]);
+            }
+            data.areas.cover = Some(chunks[0]);
+            chunks[2]
+        }
+        None => area,
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(6), Constraint::Min(0)])
+        .split(area);
+    f.render_widget(paragraph, chunks[0]);
+    draw_lyrics(f, data, chunks[1]);
+}
+
+/// Lyrics of the song playing. With timed lyrics the line being sung is
+/// highlighted and kept in the middle.
+fn draw_lyrics<B>(f: &mut Frame<B>, data: &Data, area: Rect)
+where
+    B: Backend,
+{
+    let lyrics = match &data.lyrics.lyrics {
+        Some(lyrics) if !lyrics.lines.is_empty() && area.height > 1 => lyrics,
+        _ => return,
+    };
+    let elapsed = data
+        .status
+        .elapsed
+        .map_or(0, |e| e.num_milliseconds().max(0));
+    let current = lyrics.current(elapsed as u64);
+    let sung = Style::default()
+        .fg(data.colors.highlight)
+        .add_modifier(Modifier::BOLD);
+    let lines: Vec<Spans> = lyrics
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let style = if current == Some(i) {
+                sung
+            } else {
+                Style::default()
+            };
+            Spans::from(vec![
+                Span::from("  "),
+                Span::styled(line.text.clone(), style),
+            ])
+        })
+        .collect();
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(area);
+    let title = Paragraph::new(Span::styled(
+        " Lyrics:",
+        Style::default().fg(data.colors.highlight),
+    ))
+    .style(data.style);
+    f.render_widget(title, chunks[0]);
+    let scroll = current.map_or(0, |line| line.saturating_sub(chunks[1].height as usize / 2));
+    let paragraph = Paragraph::new(lines)
+        .style(data.style)
+        .scroll((scroll as u16, 0));
+    f.render_widget(paragraph, chunks[1]);
+}
+
+fn draw_offline<B>(f: &mut Frame<B>, data: &mut Data, area: Rect)
+where
+    B: Backend,
+{
+    let offline = match &data.offline {
+        Some(offline) => offline,
+        None => return,
+    };
+    let retry = offline.retry_in();
+    let text = vec![
+        Spans::from(Span::styled(
+            " Disconnected:",
+            Style::default().fg(data.colors.highlight),
+        )),
+        Spans::from(vec![Span::from("  "), Span::from(offline.target.clone())]),
+        Spans::from(vec![Span::from("  "), Span::from(offline.error.clone())]),
+        Spans::from(""),
+        Spans::from(format!(
+            " Retrying in {}.{}s (attempt {})",
+            retry.as_secs(),
+            retry.subsec_millis() / 100,
+            offline.attempts + 1
+        )),
     ];
     let paragraph = Paragraph::new(text)
         .block(Block::default().style(data.style))
@@ -109,6 +390,52 @@ where
     f.render_widget(paragraph, area);
 }
 
+fn draw_finder<B>(f: &mut Frame<B>, data: &mut Data, area: Rect)
+where
+    B: Backend,
+{
+    let finder = match data.finder.as_mut() {
+        Some(finder) => finder,
+        None => return,
+    };
+    let block = Block::default()
+        .style(data.style)
+        .borders(Borders::ALL)
+        .title(Span::styled(
+            if finder.loading {
+                String::from(" loading songs… ")
+            } else {
+                format!(" {} songs ", finder.results.items.len())
+            },
+            Style::default().fg(data.colors.highlight),
+        ));
+    let inner = block.inner(area);
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(inner);
+    let prompt = Paragraph::new(Spans::from(vec![
+        Span::styled(" > ", Style::default().fg(data.colors.highlight)),
+        Span::from(finder.query.clone()),
+        Span::from("_"),
+    ]))
+    .style(data.style);
+    f.render_widget(prompt, chunks[0]);
+
+    let list = Tree::new(&finder.results.items)
+        .block(Block::default().style(data.style).borders(Borders::NONE))
+        .highlight_style(Style::default().fg(data.colors.highlight))
+        .match_style(
+            Style::default()
+                .fg(data.colors.highlight)
+                .add_modifier(Modifier::BOLD),
+        );
+    f.render_stateful_widget(list, chunks[1], &mut finder.results.state);
+    data.areas.finder = chunks[1];
+}
+
 fn draw_library<B>(f: &mut Frame<B>, data: &mut Data, area: Rect)
 where
     B: Backend,
@@ -117,14 +444,23 @@ where
         .block(Block::default().style(data.style).borders(Borders::NONE))
         .highlight_style(Style::default().fg(data.colors.highlight));
     f.render_stateful_widget(list, area, &mut data.library.state);
+    data.areas.list = area;
 }
 
 fn draw_playlists<B>(f: &mut Frame<B>, data: &mut Data, area: Rect)
 where
     B: Backend,
 {
+    let mut block = Block::default().style(data.style).borders(Borders::NONE);
+    if let Some(name) = &data.playlist {
+        block = block.title(Span::styled(
+            format!(" {} ", name),
+            Style::default().fg(data.colors.highlight),
+        ));
+    }
+    data.areas.list = block.inner(area);
     let list = Tree::new(&data.playlists.items)
-        .block(Block::default().style(data.style).borders(Borders::NONE))
+        .block(block)
         .highlight_style(Style::default().fg(data.colors.highlight));
     f.render_stateful_widget(list, area, &mut data.playlists.state);
 }
@@ -133,10 +469,32 @@ fn draw_queue<B>(f: &mut Frame<B>, data: &mut Data, area: Rect)
 where
     B: Backend,
 {
+    let chunks = Layout::default()
+        .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(area);
+    // Rows are indented by one column in the tree.
+    let width = area.width.saturating_sub(1);
+    let widths = queue::widths(&data.columns, &data.queue.items, width);
+
+    let mut header = String::from(" ");
+    for (column, &width) in data.columns.iter().zip(&widths) {
+        let name: String = column.header().chars().take(width as usize).collect();
+        header.push_str(&format!("{:w$}", name, w = (width + GAP) as usize));
+    }
+    let header = Paragraph::new(Span::styled(
+        header,
+        data.style.add_modifier(Modifier::BOLD),
+    ));
+    f.render_widget(header, chunks[0]);
+
     let list = Tree::new(&data.queue.items)
         .block(Block::default().style(data.style).borders(Borders::NONE))
-        .highlight_style(Style::default().fg(data.colors.highlight));
-    f.render_stateful_widget(list, area, &mut data.queue.state);
+        .highlight_style(Style::default().fg(data.colors.highlight))
+        .columns(widths)
+        .marked(data.selection.rows(&data.queue))
+        .marked_style(Style::default().add_modifier(Modifier::REVERSED));
+    f.render_stateful_widget(list, chunks[1], &mut data.queue.state);
+    data.areas.list = chunks[1];
 }
 
 fn draw_settings<B>(f: &mut Frame<B>, data: &mut Data, area: Rect)
@@ -161,7 +519,7 @@ where
     f.render_stateful_widget(list, layout[0], &mut data.settings.state)
 }
 
-fn draw_position<B>(f: &mut Frame<B>, data: &Data, area: Rect)
+fn draw_position<B>(f: &mut Frame<B>, data: &mut Data, area: Rect)
 where
     B: Backend,
 {
@@ -186,8 +544,9 @@ where
                 .fg(data.colors.highlight)
                 .bg(data.colors.background),
         )
-        .ratio(pos as f64 / full as f64);
+        .ratio(data.drag.unwrap_or(pos as f64 / full as f64));
     f.render_widget(gauge, chunks[0]);
+    data.areas.position = chunks[0];
 }
 
 fn draw_status_bar<B>(f: &mut Frame<B>, data: &Data, area: Rect)
@@ -208,8 +567,15 @@ where
 
     let time = time::Duration::seconds(0);
     let status = &data.status;
-    let elapsedmin = status.elapsed.unwrap_or(time).num_minutes();
-    let elapsedsec = status.elapsed.unwrap_or(time).num_seconds() % 60;
+    // While dragging over the progress bar, show where the seek would land.
+    let elapsed = match (data.drag, status.duration) {
+        (Some(ratio), Some(duration)) => Some(time::Duration::seconds(
+            (duration.num_seconds() as f64 * ratio) as i64,
+        )),
+        _ => status.elapsed,
+    };
+    let elapsedmin = elapsed.unwrap_or(time).num_minutes();
+    let elapsedsec = elapsed.unwrap_or(time).num_seconds() % 60;
     let durationmin = status.duration.unwrap_or(time).num_minutes();
     let durationsec = status.duration.unwrap_or(time).num_seconds() % 60;
 
@@ -225,14 +591,75 @@ where
         .alignment(Alignment::Right);
     f.render_widget(text, chunks[2]);
 
+    if let Some(prompt) = data.prompt.as_ref() {
+        let text = Paragraph::new(Spans::from(vec![
+            Span::styled(
+                prompt.label.as_str(),
+                Style::default().fg(data.colors.highlight),
+            ),
+            Span::from(prompt.text.clone()),
+            Span::from("_"),
+        ]))
+        .block(Block::default().style(data.style).borders(Borders::NONE))
+        .alignment(Alignment::Left);
+        f.render_widget(text, area);
+        return;
+    }
+
+    if let Some(search) = data.search.as_ref().filter(|s| s.editing) {
+        let prompt = if search.server { " search: " } else { " /" };
+        let text = Paragraph::new(Spans::from(vec![
+            Span::styled(prompt, Style::default().fg(data.colors.highlight)),
+            Span::from(search.query.clone()),
+            Span::from("_"),
+        ]))
+        .block(Block::default().style(data.style).borders(Borders::NONE))
+        .alignment(Alignment::Left);
+        f.render_widget(text, area);
+        return;
+    }
+
+    if let Some((notice, _)) = &data.notice {
+        let text = Paragraph::new(Span::styled(
+            notice.clone(),
+            Style::default().fg(data.colors.highlight),
+        ))
+        .block(Block::default().style(data.style).borders(Borders::NONE))
+        .alignment(Alignment::Center);
+        f.render_widget(text, chunks[1]);
+        return;
+    }
+
     let current = match data.current.title.clone() {
         Some(song) => String::from(song),
         None => String::from(""),
     };
+    if data.tabindex == 3 && !data.selection.is_empty() {
+        let rows = data.selection.rows(&data.queue).len();
+        let mode = if data.selection.anchor.is_some() {
+            "-- VISUAL --"
+        } else {
+            "-- SELECT --"
+        };
+        let text = Paragraph::new(format!(" {} {} selected", mode, rows))
+            .block(Block::default().style(data.style).borders(Borders::NONE))
+            .alignment(Alignment::Left);
+        f.render_widget(text, chunks[1]);
+        return;
+    }
     let text = Paragraph::new(current)
         .block(Block::default().style(data.style).borders(Borders::NONE))
         .alignment(Alignment::Center);
     f.render_widget(text, chunks[1]);
+
+    if data.busy {
+        let text = Paragraph::new(Span::styled(
+            " …",
+            Style::default().fg(data.colors.highlight),
+        ))
+        .style(data.style);
+        f.render_widget(text, Rect::new(chunks[1].x, chunks[1].y, 2, 1));
+    }
 }
 
 fn draw_options<B>(f: &mut Frame<B>, data: &mut Data, area: Rect)
@@ -244,17 +671,33 @@ where
         .horizontal_margin(3)
         .split(area);
     let block = Block::default().style(data.style).borders(Borders::NONE);
-    let items: Vec<ListItem> = data
-        .settings
-        .items
+    let width = modes::ROWS
         .iter()
-        .map(|i| ListItem::new(Spans::from(i.clone())))
+        .map(|row| row.label().len())
+        .max()
+        .unwrap_or(0);
+    let items: Vec<ListItem> = modes::ROWS
+        .iter()
+        .map(|row| {
+            ListItem::new(Spans::from(vec![
+                Span::from(format!(" {:<1$}  ", row.label(), width)),
+                Span::styled(row.value(data), Style::default().fg(data.colors.highlight)),
+            ]))
+        })
         .collect();
     let list = List::new(items)
-        .block(Block::default().style(data.style).borders(Borders::ALL))
-        .highlight_style(Style::default().fg(data.colors.highlight));
+        .block(
+            Block::default()
+                .title(" Options ")
+                .style(data.style)
+                .borders(Borders::ALL),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    if data.options_state.selected().is_none() {
+        data.options_state.select(Some(0));
+    }
     f.render_widget(block, area);
-    f.render_stateful_widget(list, layout[0], &mut data.settings.state)
+    f.render_stateful_widget(list, layout[0], &mut data.options_state)
 }
 
 fn status_icon<'a>(status: &Status) -> &'a str {
@@ -268,12 +711,28 @@ fn status_icon<'a>(status: &Status) -> &'a str {
     }
 }
 
-fn repeat_shuffle<'a>(status: &Status) -> Vec<Span> {
-    let mut out: &str = "";
-    if status.repeat {
-        out = "凌"
-    } else if !status.single {
-        out = " 綾"
-    }
-    vec![Span::from(out)]
+/// One letter for each playback mode, shown while the mode is on: repeat,
+/// random, single (`o` for oneshot), consume and crossfade.
+fn modes<'a>(data: &Data) -> Vec<Span<'a>> {
+    let on = Style::default().fg(data.colors.highlight);
+    let off = Style::default().add_modifier(Modifier::DIM);
+    let single = if data.modes.single == Single::Oneshot {
+        "o"
+    } else {
+        "s"
+    };
+    let crossfade = data.status.crossfade.is_some_and(|c| c.num_seconds() > 0);
+    [
+        ("r", data.status.repeat),
+        ("z", data.status.random),
+        (single, data.modes.single != Single::Off),
+        ("c", data.status.consume),
+        ("x", crossfade),
+    ]
+    .iter()
+    .map(|&(letter, lit)| match lit {
+        true => Span::styled(letter, on),
+        false => Span::styled("-", off),
+    })
+    .collect()
 }
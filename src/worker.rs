@@ -0,0 +1,306 @@
+use crate::art::{self, Lookup};
+use crate::config::Directories;
+use crate::connection::{self, Client, Connection};
+use crate::eve::Event;
+use crate::finder::{self, Finder};
+use crate::image::Image;
+use crate::library::LibItem;
+use crate::libs::{self, Data, Libraries};
+use crate::lrc::Lyrics;
+use crate::lyrics;
+use crate::search;
+
+use mpd::error::Result;
+use std::collections::HashMap;
+use std::io;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use termion::event::Key;
+use tokio::runtime::Builder;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::task;
+
+/// A slow command handed to the worker instead of running on the UI thread.
+pub enum Request {
+    /// Database search for the library prompt.
+    Search(String),
+    /// Songs with a tag of exactly the given value, searched for from the
+    /// info popup.
+    Tag(String, String),
+    /// Every song in the database, for the finder.
+    Index,
+    /// The library lists, after the database changed.
+    Library,
+    /// Cover art of the album playing.
+    Art(Lookup),
+    /// Lyrics of the song playing.
+    Lyrics(lyrics::Lookup),
+}
+
+impl Request {
+    /// Requests of the same kind supersede each other.
+    fn kind(&self) -> Kind {
+        match self {
+            Request::Search(_) | Request::Tag(..) => Kind::Search,
+            Request::Index => Kind::Index,
+            Request::Library => Kind::Library,
+            Request::Art(_) => Kind::Art,
+            Request::Lyrics(_) => Kind::Lyrics,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Search,
+    Index,
+    Library,
+    Art,
+    Lyrics,
+}
+
+pub enum Reply {
+    Search(Vec<LibItem>),
+    Index(finder::Index),
+    Library(Libraries),
+    /// The album and its picture, if one was found.
+    Art(String, Option<Image>),
+    /// The song and its lyrics, if any were found.
+    Lyrics(String, Option<Lyrics>),
+}
+
+/// Latest request id sent for each kind. Anything older is stale.
+type Latest = Arc<Mutex<HashMap<Kind, u64>>>;
+
+/// Requests of one kind with their ids, in the order sent.
+type Lane = UnboundedSender<(u64, Request)>;
+
+/// Called between the commands of a request that takes several. It fails once
+/// the request is stale, so the rest are not sent.
+pub type Check<'a> = &'a dyn Fn() -> Result<()>;
+
+/// Runs requests off the UI thread and sends the replies back as
+/// `Event::Done`. Each kind runs in a lane of its own, one request at a time
+/// on its own connection, so a slow index of the database does not hold up
+/// cover art or a search.
+///
+/// A request is stale once another of the same kind has been sent. Stale
+/// requests still waiting are skipped, and those running stop before their
+/// next command to mpd, as between the chunks of the finder's index. Replies
+/// to them are dropped.
+pub struct Worker {
+    tx: UnboundedSender<(u64, Request)>,
+    latest: Latest,
+    next: u64,
+    /// Kinds waiting for a reply.
+    in_flight: Vec<Kind>,
+}
+
+impl Worker {
+    pub fn spawn(
+        connection: Connection,
+        directories: Directories,
+        events: Sender<Event<Key>>,
+    ) -> Worker {
+        let (tx, mut rx) = mpsc::unbounded_channel::<(u64, Request)>();
+        let latest: Latest = Arc::default();
+        let stale = latest.clone();
+        thread::spawn(move || {
+            let runtime = match Builder::new_current_thread().build() {
+                Ok(runtime) => runtime,
+                Err(_) => return,
+            };
+            runtime.block_on(async move {
+                let mut lanes: HashMap<Kind, Lane> = HashMap::new();
+                while let Some((id, request)) = rx.recv().await {
+                    let lane = lanes.entry(request.kind()).or_insert_with(|| {
+                        lane(
+                            connection.clone(),
+                            directories.clone(),
+                            events.clone(),
+                            stale.clone(),
+                        )
+                    });
+                    let _ = lane.send((id, request));
+                }
+            });
+        });
+        Worker {
+            tx,
+            latest,
+            next: 0,
+            in_flight: vec![],
+        }
+    }
+
+    pub fn send(&mut self, request: Request) {
+        self.next += 1;
+        let kind = request.kind();
+        if let Ok(mut latest) = self.latest.lock() {
+            latest.insert(kind, self.next);
+        }
+        if !self.in_flight.contains(&kind) {
+            self.in_flight.push(kind);
+        }
+        // The worker only goes away with the whole program.
+        let _ = self.tx.send((self.next, request));
+    }
+
+    /// Whether the reply to request `id` is still wanted.
+    pub fn accept(&mut self, kind: Kind, id: u64) -> bool {
+        if is_stale(&self.latest, kind, id) {
+            return false;
+        }
+        self.in_flight.retain(|&k| k != kind);
+        true
+    }
+
+    pub fn busy(&self) -> bool {
+        !self.in_flight.is_empty()
+    }
+}
+
+/// Starts the task running the requests of one kind.
+fn lane(
+    connection: Connection,
+    directories: Directories,
+    events: Sender<Event<Key>>,
+    stale: Latest,
+) -> Lane {
+    let (tx, mut rx) = mpsc::unbounded_channel::<(u64, Request)>();
+    task::spawn(async move {
+        let mut client: Option<Client> = None;
+        while let Some((id, request)) = rx.recv().await {
+            let kind = request.kind();
+            if is_stale(&stale, kind, id) {
+                continue;
+            }
+            let connection = connection.clone();
+            let directories = directories.clone();
+            let latest = stale.clone();
+            let mut taken = client.take();
+            let blocking = task::spawn_blocking(move || {
+                let check = || {
+                    if is_stale(&latest, kind, id) {
+                        return Err(superseded());
+                    }
+                    Ok(())
+                };
+                let reply = run(&mut taken, &connection, &directories, &request, &check);
+                (taken, reply)
+            });
+            let reply = match blocking.await {
+                Ok((c, reply)) => {
+                    client = c;
+                    reply
+                }
+                // The client went down with the request; the next one
+                // connects afresh.
+                Err(err) => Err(panicked(err)),
+            };
+            if events.send(Event::Done(kind, id, reply)).is_err() {
+                return;
+            }
+        }
+    });
+    tx
+}
+
+/// Stands in for the reply of a request that panicked, so it still counts as
+/// answered.
+fn panicked(err: task::JoinError) -> mpd::error::Error {
+    let message = format!("request failed: {}", err);
+    io::Error::other(message).into()
+}
+
+/// Ends a request that went stale while it ran. Its reply is dropped, so this
+/// is never shown.
+fn superseded() -> mpd::error::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "superseded").into()
+}
+
+fn is_stale(latest: &Latest, kind: Kind, id: u64) -> bool {
+    match latest.lock() {
+        Ok(latest) => latest.get(&kind).is_some_and(|&last| last != id),
+        Err(_) => false,
+    }
+}
+
+/// Runs `request`. mpd drops connections that stay quiet for a while, so a
+/// request that fails on one kept from before is tried once more on a new
+/// one.
+fn run(
+    client: &mut Option<Client>,
+    connection: &Connection,
+    directories: &Directories,
+    request: &Request,
+    check: Check,
+) -> Result<Reply> {
+    let reused = client.is_some();
+    match ask(client, connection, directories, request, check) {
+        Err(err) if reused && connection::is_disconnect(&err) => {
+            ask(client, connection, directories, request, check)
+        }
+        reply => reply,
+    }
+}
+
+/// Runs `request`, connecting first if it needs the client. The connection is
+/// dropped when it fails so the next request starts over with a new one.
+fn ask(
+    client: &mut Option<Client>,
+    connection: &Connection,
+    directories: &Directories,
+    request: &Request,
+    check: Check,
+) -> Result<Reply> {
+    let c = match request {
+        // Pictures come as binary replies the client cannot read, so art and
+        // lyrics open connections of their own.
+        Request::Art(lookup) => {
+            let image = art::fetch(connection, directories, lookup);
+            return Ok(Reply::Art(lookup.key.clone(), image));
+        }
+        Request::Lyrics(lookup) => {
+            let lyrics = lyrics::fetch(connection, directories, lookup);
+            return Ok(Reply::Lyrics(lookup.file.clone(), lyrics));
+        }
+        _ => match client {
+            Some(c) => c,
+            None => client.insert(connection.connect()?),
+        },
+    };
+    let reply = match request {
+        Request::Search(query) => search::find(c, query, check).map(Reply::Search),
+        Request::Tag(tag, value) => search::find_tag(c, tag, value).map(Reply::Search),
+        Request::Index => finder::index(c, check).map(Reply::Index),
+        Request::Library => libs::libraries(c, check).map(Reply::Library),
+        Request::Art(_) | Request::Lyrics(_) => unreachable!("answered above"),
+    };
+    if let Err(err) = &reply {
+        if connection::is_disconnect(err) {
+            *client = None;
+        }
+    }
+    reply
+}
+
+/// Puts a reply to a request of `kind` into place.
+pub fn apply(kind: Kind, reply: Result<Reply>, data: &mut Data) {
+    match reply {
+        Ok(Reply::Search(items)) => search::results(data, items),
+        Ok(Reply::Index(index)) => Finder::loaded(data, index),
+        Ok(Reply::Library(libraries)) => data.set_libraries(libraries),
+        Ok(Reply::Art(key, image)) => data.art.loaded(key, image),
+        Ok(Reply::Lyrics(file, lyrics)) => lyrics::loaded(data, file, lyrics),
+        // Failed requests leave things as they were, but for the finder,
+        // which would otherwise wait on its index for good.
+        Err(err) => {
+            if kind == Kind::Index {
+                Finder::failed(data);
+            }
+            data.notify(err.to_string());
+        }
+    }
+}